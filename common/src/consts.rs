@@ -12,6 +12,7 @@ pub const BABYLON_START_EPOCH: u64 = 0; // Default value if no feature is enable
 
 
 pub const EPOCH_OF_YEAR: u64 = 105120; // 5*24*7*52
+pub const SECONDS_OF_YEAR: u64 = 31536000; // 60*60*24*365
 pub const A_WEEK_EPOCHS: u64 = 2016; //60/5*24*7;
 pub const RESERVE_WEEKS: usize = 4;
 pub const TO_INFINITY: WithdrawStrategy = WithdrawStrategy::Rounded(RoundingMode::ToPositiveInfinity);
@@ -4,6 +4,7 @@ pub mod vaults;
 pub mod nft_vaults;
 pub mod consts;
 pub mod utils;
+pub mod pause;
 
 
 pub use nft_vaults::*;
@@ -11,3 +12,4 @@ pub use list::*;
 pub use vaults::*;
 pub use consts::*;
 pub use utils::*;
+pub use pause::*;
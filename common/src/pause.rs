@@ -0,0 +1,22 @@
+use scrypto::prelude::*;
+
+/**
+ * The operations that can be paused by the guardian independently per asset.
+ */
+#[derive(ScryptoSbor, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Operation {
+    Supply,
+    Withdraw,
+    Borrow,
+    Liquidation,
+    FlashLoan,
+    Join,
+    Redeem
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SetPausedEvent {
+    pub asset: ResourceAddress,
+    pub operation: Operation,
+    pub paused: bool
+}
@@ -179,13 +179,14 @@ async def borrow(gateway: Gateway, session: ClientSession, network_name: str, co
                 dx_token: str, dx_amount: str, borrow_token:str, borrow_amount:str,
                 quote:str, _quote: str):
     cdp_mgr = config_data['CDP_COMPONENT']
-    (price1, quote1, timestamp1, signature1, epoch1) = await get_price_signature(session, network_name, "xrd", quote)
-    if not _quote:
-        (price2, quote2, timestamp2, signature2, epoch2) = (None, None, None, None, '')
-    else:
-        (price2, quote2, timestamp2, signature2, epoch2) = await get_price_signature(session, network_name, "xrd", _quote)
-    print("get_price_signature", price1, quote1, timestamp1, signature1, epoch1)
-    print("get_price_signature2", price2, quote2, timestamp2, signature2, epoch2)
+    quotes = []
+    for q in [quote, _quote]:
+        if not q:
+            continue
+        (price, quote_addr, timestamp, signature, epoch) = await get_price_signature(session, network_name, "xrd", q)
+        print("get_price_signature", price, quote_addr, timestamp, signature, epoch)
+        quotes.append(f'Tuple(Address("{quote_addr}"), "{price}", {timestamp}u64, "{signature}")')
+    price_bundle = f'Tuple(Array<Tuple>({", ".join(quotes)}))'
     manifest = f'''
         CALL_METHOD
             Address("{account.as_str()}")
@@ -209,14 +210,7 @@ async def borrow(gateway: Gateway, session: ClientSession, network_name: str, co
             Bucket("bucket1")
             Address("{borrow_token}")
             Decimal("{borrow_amount}")
-            "{price1}"
-            Address("{quote1}")
-            {timestamp1}u64
-            "{signature1}"
-            {price2}
-            {quote2}
-            {timestamp2}
-            {signature2}
+            {price_bundle}
         ;
         CALL_METHOD
             Address("{account.as_str()}")
@@ -135,10 +135,6 @@ export price1="0.056259787085"
 export quote1=$usdt
 export timestamp1=1700658816
 export signature1=$(python ../deploy/sign-util.py $xrd $quote1 $price1 $epoch $timestamp1)
-export price2=None
-export quote2=None
-export timestamp2=None
-export signature2=None
 export account=$p1
 export dx_token=$dx_xrd
 export amount=2000
@@ -154,10 +150,6 @@ export price1="0.056259787085"
 export quote1=$usdc
 export timestamp1=1700658816
 export signature1=$(python ../deploy/sign-util.py $xrd $quote1 $price1 $epoch $timestamp1)
-export price2=None
-export quote2=None
-export timestamp2=None
-export signature2=None
 export account=$p3
 export dx_token=$dx_usdc
 export amount=20
@@ -100,7 +100,7 @@ mod oracle_price{
             let epoch_at = Runtime::current_epoch().number();
             let quote = self.price_map.get(&res_addr).unwrap();
             if quote.epoch_at == epoch_at{
-                quote.price;
+                return quote.price;
             }
             Decimal::ZERO
         }
@@ -135,7 +135,10 @@ mod oracle_price{
             }
             
             // XRD/USDT --> USDT/XRD
-            Decimal::ONE.checked_div(Decimal::from_str(&xrd_price_in_quote).expect("incorrect price string.")).unwrap()
+            let price_in_xrd = Decimal::ONE.checked_div(Decimal::from_str(&xrd_price_in_quote).expect("incorrect price string.")).unwrap();
+            // keep the verified price as the cached quote of current epoch.
+            self.price_map.insert(quote_addr, QuotePrice { price: price_in_xrd, epoch_at });
+            price_in_xrd
             // if let Ok(xrd_price_in_res) = Decimal::from_str(){
             //     info!("price verify passed. :)");
             //     
@@ -2,7 +2,11 @@ use scrypto::prelude::*;
 use common::*;
 use common::utils::assert_resource;
 use interest::InterestModel;
+use crate::pool::lending::AccrualMode;
 use crate::pool::lending::lend_pool::LendResourcePool;
+use crate::pool::staking::staking_pool::StakingResourePool;
+use crate::treasury::RevenueSource;
+use crate::treasury::treasury::Treasury;
 
 
 #[derive(ScryptoSbor, NonFungibleData)]
@@ -12,32 +16,184 @@ pub struct FlashLoanData{
     pub fee: Decimal
 }
 
+#[derive(ScryptoSbor, Clone)]
+pub struct FlashLoanLeg{
+    pub res_addr: ResourceAddress,
+    pub amount: Decimal,
+    pub fee: Decimal
+}
+
 #[derive(ScryptoSbor, NonFungibleData)]
-pub struct CollateralDebtPosition{
-    pub borrow_token: ResourceAddress,
-    pub collateral_token: ResourceAddress,
+pub struct FlashLoanBatchData{
+    pub legs: Vec<FlashLoanLeg>
+}
 
-    #[mutable]
+/// A price quote signed by the price oracle, the price is XRD/quote (the amount of quote token per XRD).
+#[derive(ScryptoSbor, Clone)]
+pub struct SignedQuote{
+    pub quote: ResourceAddress,
+    pub price: String,
+    pub timestamp: u64,
+    pub signature: String
+}
+
+/// The signed price quotes of all tokens (except XRD) involved in a CDP operation.
+#[derive(ScryptoSbor, Clone)]
+pub struct PriceBundle{
+    pub quotes: Vec<SignedQuote>
+}
+
+impl PriceBundle {
+    pub fn get_quote(&self, token: ResourceAddress) -> Option<&SignedQuote> {
+        self.quotes.iter().find(|signed_quote| signed_quote.quote == token)
+    }
+}
+
+#[derive(ScryptoSbor, Clone)]
+pub struct DebtPosition{
     pub is_stable: bool,
     //The total amount borrowed from the user's perspective.
-    #[mutable]
     pub total_borrow: Decimal,
     //The total amount repaid from the user's perspective.
-    #[mutable]
     pub total_repay: Decimal,
-    
-    #[mutable]
+
+    // for variable
     pub normalized_borrow: Decimal,
+
+    // for stable
+    pub borrow_amount: Decimal,
+    // the accrual point of the borrow pool: the epoch number, or the clock seconds in the timestamp accrual mode.
+    pub last_update_epoch: u64,
+    pub stable_rate: Decimal,
+}
+
+impl DebtPosition {
+    pub fn new(is_stable: bool) -> Self {
+        Self{
+            is_stable,
+            total_borrow: Decimal::ZERO,
+            total_repay: Decimal::ZERO,
+            normalized_borrow: Decimal::ZERO,
+            borrow_amount: Decimal::ZERO,
+            last_update_epoch: 0u64,
+            stable_rate: Decimal::ZERO
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.normalized_borrow.is_zero() && self.borrow_amount.is_zero()
+    }
+}
+
+#[derive(ScryptoSbor, NonFungibleData)]
+pub struct CollateralDebtPosition{
+    // collateral token(dx token, LSU or dseXRD) ==> collateral amount, I.E.: dxXRD ==> 1000
     #[mutable]
-    pub collateral_amount: Decimal,
+    pub collaterals: HashMap<ResourceAddress, Decimal>,
+    // borrow token ==> debt, I.E.: xUSDC ==> DebtPosition(xUSDC)
     #[mutable]
-    pub borrow_amount: Decimal,
-    
-    // for stable
+    pub debts: HashMap<ResourceAddress, DebtPosition>,
+    // the efficiency mode category the CDP opts into.
     #[mutable]
-    pub last_update_epoch: u64,
+    pub emode_category: Option<u8>,
+}
+
+impl CollateralDebtPosition {
+    pub fn get_collateral_amount(&self, collateral_token: &ResourceAddress) -> Decimal {
+        self.collaterals.get(collateral_token).copied().unwrap_or(Decimal::ZERO)
+    }
+
+    pub fn add_collateral(&mut self, collateral_token: ResourceAddress, amount: Decimal) {
+        let latest = self.get_collateral_amount(&collateral_token).checked_add(amount).unwrap();
+        self.collaterals.insert(collateral_token, latest);
+    }
+
+    pub fn remove_collateral(&mut self, collateral_token: ResourceAddress, amount: Decimal) {
+        let current = self.get_collateral_amount(&collateral_token);
+        assert!(current >= amount, "Insufficient collateral in the CDP.");
+        let latest = current.checked_sub(amount).unwrap();
+        if latest.is_zero() {
+            self.collaterals.remove(&collateral_token);
+        } else {
+            self.collaterals.insert(collateral_token, latest);
+        }
+    }
+
+    pub fn has_debt(&self) -> bool {
+        self.debts.values().any(|debt| !debt.is_empty())
+    }
+}
+
+/// The badge allows the holder to borrow against the CDP of another owner.
+#[derive(ScryptoSbor, NonFungibleData)]
+pub struct CreditDelegation{
+    pub cdp_id: u64,
+    pub borrow_token: ResourceAddress,
+    // the remaining amount of the borrow token that can be borrowed by the holder.
     #[mutable]
-    pub stable_rate: Decimal,
+    pub allowance: Decimal
+}
+
+#[derive(ScryptoSbor)]
+pub struct CdpStatus{
+    // all values are denominated in XRD
+    pub collateral_value: Decimal,
+    pub debt_value: Decimal,
+    pub health_factor: Decimal,
+    // the value that can still be borrowed against the collaterals
+    pub borrowable_value: Decimal,
+    // borrow token ==> current debt (principal and interest)
+    pub debts: HashMap<ResourceAddress, Decimal>,
+    // collateral token ==> price(in XRD) of the underlying at which the health factor drops to ONE
+    pub liquidation_prices: HashMap<ResourceAddress, Decimal>,
+}
+
+/// Efficiency mode category of correlated assets, I.E.: XRD/LSU/dseXRD, xUSDC/xUSDT
+#[derive(ScryptoSbor, Clone)]
+pub struct EModeCategory{
+    pub label: String,
+    pub ltv: Decimal,
+    pub liquidation_threshold: Decimal,
+    pub liquidation_bonus: Decimal
+}
+
+/// A risk parameter of an asset, the change of which only takes effect after the timelock.
+#[derive(ScryptoSbor, Clone, Debug)]
+pub enum RiskParam{
+    Ltv(Decimal),
+    LiquidationThreshold(Decimal),
+    LiquidationBonus(Decimal),
+    InterestModel(InterestModel),
+    // the share of the interest for the insurance of the lending pool
+    InsuranceRatio(Decimal),
+    FlashloanFeeRatio(Decimal)
+}
+
+#[derive(ScryptoSbor, Clone)]
+pub struct PendingRiskParamChange{
+    pub underlying_token: ResourceAddress,
+    pub param: RiskParam,
+    pub queued_epoch: u64,
+    // the change can be executed at or after this epoch
+    pub effective_epoch: u64
+}
+
+#[derive(ScryptoSbor, Clone, PartialEq)]
+pub enum StakingCollateralKind{
+    // LSU of the validator
+    Lsu(ComponentAddress),
+    // dseXRD of the staking pool
+    Dse(ComponentAddress)
+}
+
+/// Collateral that is staked XRD instead of a dx token, its underlying token is XRD.
+#[derive(ScryptoSbor)]
+struct StakingCollateralState{
+    pub kind: StakingCollateralKind,
+    pub ltv: Decimal,
+    pub liquidation_threshold: Decimal,
+    pub liquidation_bonus: Decimal,
+    pub emode_category: Option<u8>
 }
 
 #[derive(ScryptoSbor)]
@@ -46,7 +202,30 @@ struct AssetState{
     pub collateral_token: ResourceAddress,
     pub ltv: Decimal,
     pub liquidation_threshold: Decimal,
-    pub liquidation_bonus: Decimal
+    pub liquidation_bonus: Decimal,
+    // the maximum total supply of the underlying asset, None means unlimited.
+    pub supply_cap: Option<Decimal>,
+    // the maximum total borrow of the underlying asset, None means unlimited.
+    pub borrow_cap: Option<Decimal>,
+
+    // isolation mode: a CDP using an isolated collateral can not hold other collaterals,
+    // and can only borrow the assets which are borrowable in isolation(I.E.: stablecoins).
+    pub is_isolated: bool,
+    // the maximum total debt backed by the isolated collateral, denominated in units of the assets borrowable in isolation.
+    pub debt_ceiling: Decimal,
+    // the total debt currently backed by the isolated collateral.
+    pub isolated_debt: Decimal,
+    pub borrowable_in_isolation: bool,
+
+    // the efficiency mode category of the asset.
+    pub emode_category: Option<u8>
+}
+
+fn get_integer_id(local_id: &NonFungibleLocalId) -> u64{
+    match local_id {
+        NonFungibleLocalId::Integer(id) => id.value(),
+        _ => panic!("Unexpected non fungible id type!")
+    }
 }
 
 
@@ -56,6 +235,13 @@ struct AssetState{
     NonFungibleVault,
     FungibleVault
 )]
+#[events(
+    SetCapsEvent, SetIsolationModeEvent, SetBorrowableInIsolationEvent, SetEModeCategoryEvent, SetAssetEModeEvent, SetStakingCollateralEvent,
+    BadDebtEvent, SetFlashloanFeeSplitEvent, SetPausedEvent, NewPoolEvent, BorrowEvent, ExtendBorrowEvent, RepayEvent,
+    AddCollateralEvent, WithdrawCollateralEvent, CloseCdpEvent, LiquidationEvent, FlashLoanBorrowEvent, FlashLoanRepayEvent,
+    SwapRateModeEvent, RebalanceStableRateEvent, MergeCdpsEvent, SplitCdpEvent, SetCdpEModeEvent, DelegateCreditEvent, RevokeCreditEvent,
+    QueueRiskParamChangeEvent, ExecuteRiskParamChangeEvent, CancelRiskParamChangeEvent, SetAccrualModeEvent
+)]
 mod cdp_mgr{
 
     // const INTEREST_COMPONENT: ComponentAddress = _INTEREST_COMPONENT;
@@ -80,29 +266,68 @@ mod cdp_mgr{
             authority => updatable_by:[]; 
             admin => updatable_by: [authority];
             operator => updatable_by: [authority];
+            guardian => updatable_by: [authority];
             protocol_caller => updatable_by:[];
         },
         methods{
             new_pool => restrict_to:[admin];
+            set_staking_collateral => restrict_to:[admin];
+            set_isolation_mode => restrict_to:[admin];
+            set_borrowable_in_isolation => restrict_to:[admin];
+            set_emode_category => restrict_to:[admin];
+            set_asset_emode => restrict_to:[admin];
+            unpause => restrict_to:[admin];
+            set_accrual_mode => restrict_to:[admin];
+            queue_risk_param_change => restrict_to:[admin];
+            execute_risk_param_change => restrict_to:[admin];
+            cancel_risk_param_change => restrict_to:[admin];
+            set_risk_param_timelock => restrict_to:[authority];
+            pause => restrict_to:[guardian, admin];
             withdraw_insurance => restrict_to: [operator];
+            withdraw_treasury => restrict_to: [operator];
+            set_flashloan_fee_split => restrict_to: [operator];
             set_close_factor =>restrict_to: [operator];
+            set_rebalance_ratio => restrict_to: [operator];
+            set_caps => restrict_to: [operator];
 
             staking_borrow => restrict_to: [protocol_caller];
 
             borrow_variable => PUBLIC;
             borrow_stable => PUBLIC;
             extend_borrow => PUBLIC;
+            delegate_credit => PUBLIC;
+            revoke_credit => PUBLIC;
+            extend_borrow_delegated => PUBLIC;
             withdraw_collateral => PUBLIC;
             liquidation => PUBLIC;
+            flash_liquidation => PUBLIC;
 
             borrow_flashloan => PUBLIC;
             repay_flashloan => PUBLIC;
+            borrow_flashloan_batch => PUBLIC;
+            repay_flashloan_batch => PUBLIC;
             supply => PUBLIC;
             withdraw => PUBLIC;
+            request_withdrawal => PUBLIC;
+            claim_withdrawal => PUBLIC;
+            cancel_withdrawal => PUBLIC;
+            deposit_term => PUBLIC;
+            redeem_term_deposit => PUBLIC;
             repay => PUBLIC;
             addition_collateral => PUBLIC;
+            close_cdp => PUBLIC;
+            merge_cdps => PUBLIC;
+            split_cdp => PUBLIC;
+            swap_rate_mode => PUBLIC;
+            set_cdp_emode => PUBLIC;
+            rebalance_stable_rate => PUBLIC;
 
             get_interest_rate => PUBLIC;
+            get_cdp_status => PUBLIC;
+            is_paused => PUBLIC;
+            get_pending_risk_param_changes => PUBLIC;
+            get_treasury => PUBLIC;
+            get_term_deposit_rate => PUBLIC;
         }
     }
 
@@ -111,19 +336,41 @@ mod cdp_mgr{
         pools: HashMap<ResourceAddress, Global<LendResourcePool>>,
         //Status of each asset in the lending pool, I.E.: XRD ==> AssetState(XRD)
         states: HashMap<ResourceAddress, AssetState>,
-        // vault for each collateral asset(supply token), I.E. dxXRD ==> Vault(dxXRD)
+        // efficiency mode categories, I.E.: 1 ==> EModeCategory(XRD/LSU/dseXRD)
+        emode_categories: HashMap<u8, EModeCategory>,
+        // staked XRD accepted as collateral, I.E.: LSU ==> StakingCollateralState(LSU)
+        staking_collaterals: HashMap<ResourceAddress, StakingCollateralState>,
+        // vault for each collateral asset(supply token, LSU or dseXRD), I.E. dxXRD ==> Vault(dxXRD)
         collateral_vaults: Vaults,
         self_cmp_addr: ComponentAddress,
         // CDP token define
         cdp_res_mgr: NonFungibleResourceManager,
         // CDP id counter
         cdp_id_counter: u64,
+        // credit delegation badge define
+        delegation_res_mgr: NonFungibleResourceManager,
+        // credit delegation badge counter
+        delegation_id_counter: u64,
         // close factor for liquidation
         close_factor_percent: Decimal,
+        // a stable debt can be rebalanced when supply rate > stable rate of the debt * rebalance ratio
+        rebalance_ratio: Decimal,
         /// flashloan NFT resource manager
         transient_nft_res_mgr: NonFungibleResourceManager,
+        /// batch flashloan NFT resource manager
+        batch_transient_nft_res_mgr: NonFungibleResourceManager,
         // flashloan NFT counter
         transient_id_counter: u64,
+        // the holder of the badge can flash-borrow without fee, I.E.: liquidation bot, staking earning.
+        fee_exempt_badge: ResourceAddress,
+        // the number of epochs a queued risk parameter change must wait before it can be executed
+        risk_param_timelock: u64,
+        // change id ==> risk parameter change waiting for execution
+        pending_risk_param_changes: HashMap<u64, PendingRiskParamChange>,
+        // risk parameter change id counter
+        risk_param_change_id_counter: u64,
+        // the protocol reserves: insurance and flash loan fee withdrawn from the pools
+        treasury: Global<Treasury>,
     }
 
     impl CollateralDebtManager{
@@ -159,6 +406,27 @@ mod cdp_mgr{
                 ))
                 .create_with_no_initial_supply();
 
+            let delegation_res_mgr = ResourceBuilder::new_integer_non_fungible::<CreditDelegation>(OwnerRole::None)
+                .metadata(metadata!(init{
+                    "symbol" => "dxCD", locked;
+                    "name" => "DeXian Credit Delegation Badge", locked;
+                    "icon_url" => "https://dexian.io/images/cdp.png", updatable;
+                    "info_url" => "https://dexian.io", updatable;
+                }))
+                .mint_roles(mint_roles!( 
+                    minter => rule!(require(global_caller(address)));
+                    minter_updater => rule!(deny_all);
+                ))
+                .burn_roles(burn_roles!(
+                    burner => rule!(require(global_caller(address)));
+                    burner_updater => rule!(deny_all);
+                ))
+                .non_fungible_data_update_roles(non_fungible_data_update_roles!(
+                    non_fungible_data_updater => rule!(require(global_caller(address)));
+                    non_fungible_data_updater_updater => rule!(deny_all);
+                ))
+                .create_with_no_initial_supply();
+
             let transient_nft_res_mgr = ResourceBuilder::new_integer_non_fungible::<FlashLoanData>(
                 owner_role.clone()).metadata(metadata!{
                 init{
@@ -177,17 +445,75 @@ mod cdp_mgr{
                 depositor => rule!(deny_all);
                 depositor_updater => rule!(deny_all);
             )).create_with_no_initial_supply();
+
+            let batch_transient_nft_res_mgr = ResourceBuilder::new_integer_non_fungible::<FlashLoanBatchData>(
+                owner_role.clone()).metadata(metadata!{
+                init{
+                    "name"=> "dxBatchLoanNFT", locked;
+                    "description" => "DeXian Batch FlashLoan NFT", locked;
+                    "icon_url" => "https://dexian.io/images/flash.png", updatable;
+                    "info_url" => "https://dexian.io", updatable;
+                }
+            }).mint_roles(mint_roles!(
+                minter => rule!(require(global_caller(address)));
+                minter_updater => rule!(deny_all);
+            )).burn_roles(burn_roles!(
+                burner => rule!(require(global_caller(address)));
+                burner_updater => rule!(deny_all);
+            )).deposit_roles(deposit_roles!(
+                depositor => rule!(deny_all);
+                depositor_updater => rule!(deny_all);
+            )).create_with_no_initial_supply();
+            
+            let fee_exempt_badge = ResourceBuilder::new_fungible(owner_role.clone())
+                .divisibility(DIVISIBILITY_NONE)
+                .metadata(metadata!(init{
+                    "symbol" => "dxFEB", locked;
+                    "name" => "DeXian FlashLoan Fee Exempt Badge", locked;
+                    "icon_url" => "https://dexian.io/images/flash.png", updatable;
+                    "info_url" => "https://dexian.io", updatable;
+                }))
+                .mint_roles(mint_roles!(
+                    minter => rule!(require(AUTHORITY_RESOURCE));
+                    minter_updater => rule!(deny_all);
+                ))
+                .burn_roles(burn_roles!(
+                    burner => rule!(require(AUTHORITY_RESOURCE));
+                    burner_updater => rule!(deny_all);
+                ))
+                .create_with_no_initial_supply()
+                .address();
+            
+            let treasury = Treasury::instantiate(
+                owner_role.clone(),
+                rule!(require(AUTHORITY_RESOURCE)),
+                admin_rule.clone(),
+                op_rule.clone(),
+                rule!(require(global_caller(address)) || require(global_caller(earning_addr)))
+            );
             
             let component = Self{
                 pools: HashMap::new(),
                 states: HashMap::new(),
+                emode_categories: HashMap::new(),
+                staking_collaterals: HashMap::new(),
                 collateral_vaults: Vaults::new(|| CollateralDebtManagerKeyValueStore::new_with_registered_type()),
                 self_cmp_addr: address,
                 close_factor_percent: Decimal::from(50),
+                rebalance_ratio: Decimal::ONE,
                 cdp_id_counter: 0u64,
+                delegation_id_counter: 0u64,
+                delegation_res_mgr,
                 transient_id_counter: 0u64,
+                fee_exempt_badge,
+                // about one day
+                risk_param_timelock: 288u64,
+                pending_risk_param_changes: HashMap::new(),
+                risk_param_change_id_counter: 0u64,
+                treasury,
                 cdp_res_mgr,
-                transient_nft_res_mgr
+                transient_nft_res_mgr,
+                batch_transient_nft_res_mgr
             }
             .instantiate()
             .prepare_to_globalize(owner_role)
@@ -196,6 +522,7 @@ mod cdp_mgr{
                 authority => rule!(require(AUTHORITY_RESOURCE));
                 admin => admin_rule.clone();
                 operator => op_rule.clone();
+                guardian => op_rule.clone();
                 protocol_caller => rule!(require(global_caller(earning_addr)));
 
             })
@@ -231,18 +558,83 @@ mod cdp_mgr{
                 collateral_token: dx_token_addr,
                 ltv,
                 liquidation_threshold,
-                liquidation_bonus
+                liquidation_bonus,
+                supply_cap: None,
+                borrow_cap: None,
+                is_isolated: false,
+                debt_ceiling: Decimal::ZERO,
+                isolated_debt: Decimal::ZERO,
+                borrowable_in_isolation: false,
+                emode_category: None
             };
             self.pools.insert(underlying_token_addr, lend_res_pool);
             self.states.insert(underlying_token_addr, asset_state);
             self.collateral_vaults.put(FungibleBucket::new(dx_token_addr));
+            Runtime::emit_event(NewPoolEvent{
+                underlying_token: underlying_token_addr,
+                dx_token: dx_token_addr,
+                ltv,
+                liquidation_threshold,
+                liquidation_bonus,
+                insurance_ratio,
+                flashloan_fee_ratio
+            });
             dx_token_addr
         }
 
+        ///
+        /// Accepts the LSU of a validator or the dseXRD of the staking pool as collateral, or updates its risk parameters.
+        /// The kind of the collateral is recognized by the metadata of the resource:
+        /// LSU carries the `validator` and dseXRD carries the `pool`.
+        pub fn set_staking_collateral(&mut self,
+            collateral_token: ResourceAddress,
+            ltv: Decimal,
+            liquidation_threshold: Decimal,
+            liquidation_bonus: Decimal
+        ){
+            assert!(ltv.is_positive() && ltv <= liquidation_threshold && liquidation_threshold < Decimal::ONE, "Incorrect LTV or liquidation threshold!");
+            assert!(
+                !self.pools.contains_key(&collateral_token) && !self.states.values().any(|state| state.collateral_token == collateral_token),
+                "The token is already supported by the lending pools!"
+            );
+            let res_mgr = ResourceManager::from_address(collateral_token);
+            let kind = if res_mgr.get_metadata::<&str, GlobalAddress>("validator").is_ok_and(|addr| addr.is_some()) {
+                let validator = get_validator(collateral_token);
+                let validator_addr = validator.address();
+                assert!(get_lsu_res_addr(validator_addr) == collateral_token, "The LSU does not match the validator!");
+                StakingCollateralKind::Lsu(validator_addr)
+            } else {
+                let addr = res_mgr.get_metadata::<&str, GlobalAddress>("pool").unwrap().expect("The token is neither LSU nor dseXRD!");
+                let pool_addr = ComponentAddress::try_from(addr).unwrap();
+                let staking_pool = Global::<StakingResourePool>::from(pool_addr);
+                assert!(staking_pool.get_underlying_token() == XRD, "The underlying token of the staking pool is not XRD!");
+                assert!(get_res_addr_from_metadata(pool_addr, "pool_unit") == collateral_token, "The dseXRD does not match the staking pool!");
+                StakingCollateralKind::Dse(pool_addr)
+            };
+
+            let emode_category = self.staking_collaterals.get(&collateral_token).and_then(|state| state.emode_category);
+            self.staking_collaterals.insert(collateral_token, StakingCollateralState{
+                kind,
+                ltv,
+                liquidation_threshold,
+                liquidation_bonus,
+                emode_category
+            });
+            self.collateral_vaults.put(FungibleBucket::new(collateral_token));
+            Runtime::emit_event(SetStakingCollateralEvent{
+                collateral_token,
+                ltv,
+                liquidation_threshold,
+                liquidation_bonus
+            });
+        }
+
         pub fn staking_borrow(&mut self, underlying_token_addr: ResourceAddress, borrow_amount: Decimal, 
             claim_nfts: Vec<NonFungibleBucket>, interests: Vec<Decimal>
         ) -> FungibleBucket{
             assert!(self.pools.contains_key(&underlying_token_addr), "There is no pool of funds corresponding to the assets!");
+            self.assert_not_paused(underlying_token_addr, Operation::Borrow);
+            self.assert_borrow_cap(underlying_token_addr, borrow_amount);
             let lending_pool = self.pools.get_mut(&underlying_token_addr).unwrap();
             let borrow_bucket = lending_pool.borrow_fixed_term(borrow_amount);
             for (claim_nft, interest) in claim_nfts.into_iter().zip(interests.into_iter()) {
@@ -251,13 +643,221 @@ mod cdp_mgr{
             borrow_bucket
         }
 
+        ///
+        /// Pauses the operation of the asset, only the admin can unpause it.
+        pub fn pause(&mut self, underlying_token_addr: ResourceAddress, operation: Operation){
+            self.set_paused(underlying_token_addr, operation, true);
+        }
+
+        pub fn unpause(&mut self, underlying_token_addr: ResourceAddress, operation: Operation){
+            self.set_paused(underlying_token_addr, operation, false);
+        }
+
+        ///
+        /// Selects the interest accrual of the pool, an existing pool can migrate from epochs to clock seconds.
+        pub fn set_accrual_mode(&mut self, underlying_token_addr: ResourceAddress, accrual_mode: AccrualMode){
+            assert!(self.pools.contains_key(&underlying_token_addr), "There is no pool of funds corresponding to the assets!");
+            self.pools.get_mut(&underlying_token_addr).unwrap().set_accrual_mode(accrual_mode);
+            Runtime::emit_event(SetAccrualModeEvent{
+                underlying_token: underlying_token_addr,
+                accrual_mode
+            });
+        }
+
+        pub fn is_paused(&self, underlying_token_addr: ResourceAddress, operation: Operation) -> bool{
+            assert!(self.pools.contains_key(&underlying_token_addr), "There is no pool of funds corresponding to the assets!");
+            self.pools.get(&underlying_token_addr).unwrap().is_paused(operation)
+        }
+
+        fn set_paused(&mut self, underlying_token_addr: ResourceAddress, operation: Operation, paused: bool){
+            assert!(self.pools.contains_key(&underlying_token_addr), "There is no pool of funds corresponding to the assets!");
+            assert!(
+                operation != Operation::Join && operation != Operation::Redeem,
+                "The operation {:?} is paused by the staking earning!", operation
+            );
+            self.pools.get_mut(&underlying_token_addr).unwrap().set_paused(operation, paused);
+            Runtime::emit_event(SetPausedEvent{
+                asset: underlying_token_addr,
+                operation,
+                paused
+            });
+        }
+
+        fn assert_not_paused(&self, underlying_token_addr: ResourceAddress, operation: Operation){
+            assert!(!self.is_paused(underlying_token_addr, operation), "The operation {:?} of the asset is paused!", operation);
+        }
+
         pub fn set_close_factor(&mut self, new_close_factor: Decimal){
             self.close_factor_percent = new_close_factor;
         }
 
+        pub fn set_rebalance_ratio(&mut self, new_rebalance_ratio: Decimal){
+            assert!(new_rebalance_ratio.is_positive(), "The rebalance ratio must be positive!");
+            self.rebalance_ratio = new_rebalance_ratio;
+        }
+
+        pub fn set_risk_param_timelock(&mut self, epochs: u64){
+            self.risk_param_timelock = epochs;
+        }
+
+        ///
+        /// Queues a change of the risk parameter of the asset, it can be executed after `risk_param_timelock` epochs.
+        /// Returns the id of the change.
+        pub fn queue_risk_param_change(&mut self, underlying_token_addr: ResourceAddress, param: RiskParam) -> u64{
+            assert!(self.states.contains_key(&underlying_token_addr), "There is no pool of funds corresponding to the assets!");
+            self.assert_risk_param(underlying_token_addr, &param);
+            let queued_epoch = Runtime::current_epoch().number();
+            let effective_epoch = queued_epoch.checked_add(self.risk_param_timelock).unwrap();
+            self.risk_param_change_id_counter += 1;
+            self.pending_risk_param_changes.insert(self.risk_param_change_id_counter, PendingRiskParamChange{
+                underlying_token: underlying_token_addr,
+                param: param.clone(),
+                queued_epoch,
+                effective_epoch
+            });
+            Runtime::emit_event(QueueRiskParamChangeEvent{
+                change_id: self.risk_param_change_id_counter,
+                underlying_token: underlying_token_addr,
+                param,
+                effective_epoch
+            });
+            self.risk_param_change_id_counter
+        }
+
+        pub fn execute_risk_param_change(&mut self, change_id: u64){
+            let change = self.pending_risk_param_changes.get(&change_id).cloned().expect("The risk parameter change does not exist!");
+            assert!(Runtime::current_epoch().number() >= change.effective_epoch, "The risk parameter change is still timelocked!");
+            // the other parameters may have changed since the change was queued.
+            self.assert_risk_param(change.underlying_token, &change.param);
+            self.pending_risk_param_changes.remove(&change_id);
+
+            let state = self.states.get_mut(&change.underlying_token).unwrap();
+            let pool = self.pools.get_mut(&change.underlying_token).unwrap();
+            match change.param.clone() {
+                RiskParam::Ltv(ltv) => state.ltv = ltv,
+                RiskParam::LiquidationThreshold(liquidation_threshold) => state.liquidation_threshold = liquidation_threshold,
+                RiskParam::LiquidationBonus(liquidation_bonus) => state.liquidation_bonus = liquidation_bonus,
+                RiskParam::InterestModel(interest_model) => {
+                    state.interest_model = interest_model.clone();
+                    pool.set_interest_model(interest_model);
+                },
+                RiskParam::InsuranceRatio(insurance_ratio) => pool.set_insurance_ratio(insurance_ratio),
+                RiskParam::FlashloanFeeRatio(flashloan_fee_ratio) => pool.set_flashloan_fee_ratio(flashloan_fee_ratio)
+            }
+            Runtime::emit_event(ExecuteRiskParamChangeEvent{
+                change_id,
+                underlying_token: change.underlying_token,
+                param: change.param
+            });
+        }
+
+        pub fn cancel_risk_param_change(&mut self, change_id: u64){
+            let change = self.pending_risk_param_changes.remove(&change_id).expect("The risk parameter change does not exist!");
+            Runtime::emit_event(CancelRiskParamChangeEvent{
+                change_id,
+                underlying_token: change.underlying_token,
+                param: change.param
+            });
+        }
+
+        pub fn get_pending_risk_param_changes(&self) -> HashMap<u64, PendingRiskParamChange>{
+            self.pending_risk_param_changes.clone()
+        }
+
+        fn assert_risk_param(&self, underlying_token_addr: ResourceAddress, param: &RiskParam){
+            let state = self.states.get(&underlying_token_addr).unwrap();
+            match param {
+                RiskParam::Ltv(ltv) => assert!(
+                    !ltv.is_negative() && *ltv <= state.liquidation_threshold,
+                    "The LTV must be between zero and the liquidation threshold!"
+                ),
+                RiskParam::LiquidationThreshold(liquidation_threshold) => assert!(
+                    *liquidation_threshold >= state.ltv && *liquidation_threshold < Decimal::ONE,
+                    "The liquidation threshold must be between the LTV and one!"
+                ),
+                RiskParam::LiquidationBonus(liquidation_bonus) => assert!(
+                    !liquidation_bonus.is_negative() && *liquidation_bonus < Decimal::ONE,
+                    "Incorrect liquidation bonus!"
+                ),
+                RiskParam::InterestModel(_) => {},
+                RiskParam::InsuranceRatio(ratio) | RiskParam::FlashloanFeeRatio(ratio) => assert!(
+                    !ratio.is_negative() && *ratio < Decimal::ONE,
+                    "The ratio must be between zero and one!"
+                )
+            }
+        }
+
+        pub fn set_caps(&mut self, underlying_token_addr: ResourceAddress, supply_cap: Option<Decimal>, borrow_cap: Option<Decimal>){
+            assert!(self.states.contains_key(&underlying_token_addr), "There is no pool of funds corresponding to the assets!");
+            let state = self.states.get_mut(&underlying_token_addr).unwrap();
+            state.supply_cap = supply_cap;
+            state.borrow_cap = borrow_cap;
+            Runtime::emit_event(SetCapsEvent{
+                underlying_token: underlying_token_addr,
+                supply_cap,
+                borrow_cap
+            });
+        }
+
+        pub fn set_isolation_mode(&mut self, underlying_token_addr: ResourceAddress, is_isolated: bool, debt_ceiling: Decimal){
+            assert!(self.states.contains_key(&underlying_token_addr), "There is no pool of funds corresponding to the assets!");
+            let state = self.states.get_mut(&underlying_token_addr).unwrap();
+            state.is_isolated = is_isolated;
+            state.debt_ceiling = debt_ceiling;
+            Runtime::emit_event(SetIsolationModeEvent{
+                underlying_token: underlying_token_addr,
+                is_isolated,
+                debt_ceiling
+            });
+        }
+
+        pub fn set_borrowable_in_isolation(&mut self, underlying_token_addr: ResourceAddress, borrowable: bool){
+            assert!(self.states.contains_key(&underlying_token_addr), "There is no pool of funds corresponding to the assets!");
+            self.states.get_mut(&underlying_token_addr).unwrap().borrowable_in_isolation = borrowable;
+            Runtime::emit_event(SetBorrowableInIsolationEvent{
+                underlying_token: underlying_token_addr,
+                borrowable
+            });
+        }
+
+        pub fn set_emode_category(&mut self, category_id: u8, label: String, ltv: Decimal, liquidation_threshold: Decimal, liquidation_bonus: Decimal){
+            assert!(ltv.is_positive() && ltv <= liquidation_threshold && liquidation_threshold < Decimal::ONE, "Incorrect LTV or liquidation threshold!");
+            self.emode_categories.insert(category_id, EModeCategory{
+                label: label.clone(),
+                ltv,
+                liquidation_threshold,
+                liquidation_bonus
+            });
+            Runtime::emit_event(SetEModeCategoryEvent{
+                category_id,
+                label,
+                ltv,
+                liquidation_threshold,
+                liquidation_bonus
+            });
+        }
+
+        /// Assigns the asset (an underlying token of the lending pools, LSU or dseXRD) to an efficiency mode category.
+        pub fn set_asset_emode(&mut self, underlying_token_addr: ResourceAddress, category_id: Option<u8>){
+            if let Some(category_id) = category_id {
+                assert!(self.emode_categories.contains_key(&category_id), "Unknown efficiency mode category!");
+            }
+            if let Some(state) = self.staking_collaterals.get_mut(&underlying_token_addr) {
+                state.emode_category = category_id;
+            } else {
+                assert!(self.states.contains_key(&underlying_token_addr), "There is no pool of funds corresponding to the assets!");
+                self.states.get_mut(&underlying_token_addr).unwrap().emode_category = category_id;
+            }
+            Runtime::emit_event(SetAssetEModeEvent{
+                underlying_token: underlying_token_addr,
+                category_id
+            });
+        }
+
         pub fn supply(&mut self, bucket: FungibleBucket) -> FungibleBucket{
             let supply_res_addr = bucket.resource_address();
             assert!(self.pools.get(&supply_res_addr).is_some(), "There is no pool of funds corresponding to the assets!");
+            self.assert_supply_cap(supply_res_addr, bucket.amount());
             let lending_pool = self.pools.get_mut(&supply_res_addr).unwrap();
             lending_pool.add_liquity(bucket)
         }
@@ -269,36 +869,90 @@ mod cdp_mgr{
             lending_pool.remove_liquity(bucket)
         }
 
+        ///
+        /// Queues the withdrawal of the dx tokens when the pool is short of liquidity, see `LendResourcePool::request_withdrawal`.
+        pub fn request_withdrawal(&mut self, bucket: FungibleBucket) -> NonFungibleBucket{
+            let underlying_token = get_underlying_token_res_addr(bucket.resource_address());
+            assert!(self.pools.contains_key(&underlying_token), "the token has not supported!");
+            let lending_pool = self.pools.get_mut(&underlying_token).unwrap();
+            lending_pool.request_withdrawal(bucket)
+        }
+
+        pub fn claim_withdrawal(&mut self, request: NonFungibleBucket) -> (FungibleBucket, Option<NonFungibleBucket>){
+            let underlying_token = get_underlying_token_res_addr(request.resource_address());
+            assert!(self.pools.contains_key(&underlying_token), "the token has not supported!");
+            let lending_pool = self.pools.get_mut(&underlying_token).unwrap();
+            lending_pool.claim_withdrawal(request)
+        }
+
+        pub fn cancel_withdrawal(&mut self, request: NonFungibleBucket) -> (FungibleBucket, FungibleBucket){
+            let underlying_token = get_underlying_token_res_addr(request.resource_address());
+            assert!(self.pools.contains_key(&underlying_token), "the token has not supported!");
+            let lending_pool = self.pools.get_mut(&underlying_token).unwrap();
+            lending_pool.cancel_withdrawal(request)
+        }
+
+        ///
+        /// Deposits for a term of 1, 4 or 12 weeks at a fixed rate, see `LendResourcePool::deposit_term`.
+        pub fn deposit_term(&mut self, bucket: FungibleBucket, weeks: u64) -> NonFungibleBucket{
+            let underlying_token = bucket.resource_address();
+            assert!(self.pools.contains_key(&underlying_token), "There is no pool of funds corresponding to the assets!");
+            self.assert_supply_cap(underlying_token, bucket.amount());
+            let lending_pool = self.pools.get_mut(&underlying_token).unwrap();
+            lending_pool.deposit_term(bucket, weeks)
+        }
+
+        pub fn redeem_term_deposit(&mut self, term_deposit: NonFungibleBucket) -> FungibleBucket{
+            let underlying_token = get_underlying_token_res_addr(term_deposit.resource_address());
+            assert!(self.pools.contains_key(&underlying_token), "the token has not supported!");
+            let lending_pool = self.pools.get_mut(&underlying_token).unwrap();
+            lending_pool.redeem_term_deposit(term_deposit)
+        }
+
+        pub fn get_term_deposit_rate(&self, underlying_token_addr: ResourceAddress, amount: Decimal) -> Decimal{
+            assert!(self.pools.contains_key(&underlying_token_addr), "There is no pool of funds corresponding to the assets!");
+            self.pools.get(&underlying_token_addr).unwrap().get_term_deposit_rate(amount)
+        }
+
+
         pub fn borrow_variable(&mut self,
             dx_bucket: FungibleBucket,
             borrow_token: ResourceAddress,
             borrow_amount: Decimal,
-            price1: String,
-            quote1: ResourceAddress,
-            timestamp1: u64,
-            signature1: String,
-            price2: Option<String>,
-            quote2: Option<ResourceAddress>,
-            timestamp2: Option<u64>,
-            signature2: Option<String>
+            price_bundle: PriceBundle
         ) -> (FungibleBucket, NonFungibleBucket){
             let dx_token = dx_bucket.resource_address();
             let dx_amount = dx_bucket.amount();
-            let (borrow_price_in_xrd, collateral_underlying_price_in_xrd) = self.extra_params(dx_token, borrow_token, &price1, quote1, timestamp1, &signature1, price2, quote2, timestamp2, signature2);
-            info!("borrow_price_in_xrd:{}, collateral_underlying_price_in_xrd:{}",borrow_price_in_xrd, collateral_underlying_price_in_xrd);
-            assert!(borrow_price_in_xrd.is_positive() && collateral_underlying_price_in_xrd.is_positive(), "Incorrect information on price signature.");
-            info!("collateral {}, amount:{}; price:{}/{}", Runtime::bech32_encode_address(dx_token), dx_amount, borrow_price_in_xrd, collateral_underlying_price_in_xrd);
-            let max_loan_amount = self.get_max_loan_amount(dx_token, dx_amount, borrow_token, borrow_price_in_xrd, collateral_underlying_price_in_xrd, Decimal::ZERO);
-            assert!(borrow_amount <= max_loan_amount, "The amount borrowed exceeds the borrowable quantity of the collateral.");
+            let mut cdp_data = self.new_cdp_data(dx_token, dx_amount);
+            let prices = self.get_prices_in_xrd(self.get_position_tokens(&cdp_data, Some(borrow_token)), &price_bundle);
+            info!("collateral {}, amount:{}; borrow {}, amount:{}", Runtime::bech32_encode_address(dx_token), dx_amount, Runtime::bech32_encode_address(borrow_token), borrow_amount);
+            self.assert_borrowable(&cdp_data, borrow_token, borrow_amount, &prices);
+            self.add_isolated_debt(&cdp_data, borrow_token, borrow_amount);
 
             self.collateral_vaults.put(dx_bucket);
             let (borrow_bucket, borrow_normalized_amount) = self.borrow_variable_from_pool(borrow_token, borrow_amount);
+            let mut debt = DebtPosition::new(false);
+            debt.total_borrow = borrow_amount;
+            debt.normalized_borrow = borrow_normalized_amount;
+            cdp_data.debts.insert(borrow_token, debt);
             //mint cdp
-            let cdp_bucket = self.new_cdp(dx_token, borrow_token, borrow_amount, dx_amount, borrow_normalized_amount, Decimal::ZERO, false);
+            let cdp_bucket = self.new_cdp(cdp_data);
+            Runtime::emit_event(BorrowEvent{
+                cdp_id: self.cdp_id_counter,
+                collateral_token: dx_token,
+                collateral_amount: dx_amount,
+                borrow_token,
+                amount: borrow_amount,
+                normalized_amount: borrow_normalized_amount,
+                loan_index: self.get_loan_index(borrow_token),
+                is_stable: false,
+                stable_rate: Decimal::ZERO
+            });
             (borrow_bucket, cdp_bucket)
         }
 
         fn borrow_variable_from_pool(&mut self, borrow_token: ResourceAddress, borrow_amount: Decimal) -> (FungibleBucket, Decimal){
+            self.assert_borrow_cap(borrow_token, borrow_amount);
             let lending_pool = self.pools.get_mut(&borrow_token).unwrap();
             lending_pool.borrow_variable(borrow_amount)
         }
@@ -307,31 +961,42 @@ mod cdp_mgr{
             dx_bucket: FungibleBucket,
             borrow_token: ResourceAddress,
             borrow_amount: Decimal,
-            price1: String,
-            quote1: ResourceAddress,
-            timestamp1: u64,
-            signature1: String,
-            price2: Option<String>,
-            quote2: Option<ResourceAddress>,
-            timestamp2: Option<u64>,
-            signature2: Option<String>
+            price_bundle: PriceBundle
         ) -> (FungibleBucket, NonFungibleBucket){
             let dx_token = dx_bucket.resource_address();
             let dx_amount = dx_bucket.amount();
-            let (borrow_price_in_xrd, collateral_underlying_price_in_xrd) = self.extra_params(dx_token, borrow_token, &price1, quote1, timestamp1, &signature1, price2, quote2, timestamp2, signature2);
-            assert!(borrow_price_in_xrd.is_positive() && collateral_underlying_price_in_xrd.is_positive(), "Incorrect information on price signature.");
-            let max_loan_amount = self.get_max_loan_amount(dx_token, dx_amount, borrow_token, borrow_price_in_xrd, collateral_underlying_price_in_xrd, Decimal::ZERO);
-            assert!(borrow_amount <= max_loan_amount, "The amount borrowed exceeds the borrowable quantity of the collateral.");
+            let mut cdp_data = self.new_cdp_data(dx_token, dx_amount);
+            let prices = self.get_prices_in_xrd(self.get_position_tokens(&cdp_data, Some(borrow_token)), &price_bundle);
+            self.assert_borrowable(&cdp_data, borrow_token, borrow_amount, &prices);
+            self.add_isolated_debt(&cdp_data, borrow_token, borrow_amount);
             
             self.collateral_vaults.put(dx_bucket);
             let (borrow_bucket, stable_rate) = self.borrow_stable_from_pool(borrow_token, borrow_amount);
+            let mut debt = DebtPosition::new(true);
+            debt.total_borrow = borrow_amount;
+            debt.borrow_amount = borrow_amount;
+            debt.stable_rate = stable_rate;
+            debt.last_update_epoch = self.get_accrual_point(borrow_token);
+            cdp_data.debts.insert(borrow_token, debt);
             
             //mint cdp
-            let cdp_bucket = self.new_cdp(dx_token, borrow_token, borrow_amount, dx_amount, Decimal::ZERO, stable_rate, true);
+            let cdp_bucket = self.new_cdp(cdp_data);
+            Runtime::emit_event(BorrowEvent{
+                cdp_id: self.cdp_id_counter,
+                collateral_token: dx_token,
+                collateral_amount: dx_amount,
+                borrow_token,
+                amount: borrow_amount,
+                normalized_amount: Decimal::ZERO,
+                loan_index: self.get_loan_index(borrow_token),
+                is_stable: true,
+                stable_rate
+            });
             (borrow_bucket, cdp_bucket)
         }
 
         fn borrow_stable_from_pool(&mut self, borrow_token: ResourceAddress, borrow_amount:Decimal) -> (FungibleBucket, Decimal){
+            self.assert_borrow_cap(borrow_token, borrow_amount);
             let lending_pool = self.pools.get_mut(&borrow_token).unwrap();
             let (_variable_rate,stable_rate,_supply_rate) = lending_pool.get_interest_rate(borrow_amount);
             let borrow_bucket = lending_pool.borrow_stable(borrow_amount, stable_rate);
@@ -340,160 +1005,611 @@ mod cdp_mgr{
 
         pub fn extend_borrow(&mut self,
             cdp: NonFungibleBucket,
+            borrow_token: ResourceAddress,
             amount: Decimal,
-            price1: String,
-            quote1: ResourceAddress,
-            timestamp1: u64,
-            signature1: String,
-            price2: Option<String>,
-            quote2: Option<ResourceAddress>,
-            timestamp2: Option<u64>,
-            signature2: Option<String>
+            is_stable: bool,
+            price_bundle: PriceBundle
         ) -> (FungibleBucket, NonFungibleBucket){
             assert_resource(&cdp.resource_address(), &self.cdp_res_mgr.address());
             assert!(cdp.amount() == Decimal::ONE, "Only one CDP can be processed at a time!");
             let cdp_id = cdp.non_fungible_local_id();
-            let cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
-            let borrow_token =  cdp_data.borrow_token;
-            let collateral_underlying_token = get_underlying_token_res_addr(cdp_data.collateral_token);
-            let (borrow_price_in_xrd, collateral_underlying_price_in_xrd) = self.get_price_in_xrd(collateral_underlying_token, borrow_token, &price1, quote1, timestamp1, &signature1, price2, quote2, timestamp2, signature2);
-            assert!(borrow_price_in_xrd.is_positive() && collateral_underlying_price_in_xrd.is_positive(), "Incorrect information on price signature.");
-            info!("collateral {}|{}, {}|{} price:{}/{}", Runtime::bech32_encode_address(collateral_underlying_token), collateral_underlying_token.to_hex(), Runtime::bech32_encode_address(collateral_underlying_token),collateral_underlying_token.to_hex() , borrow_price_in_xrd, collateral_underlying_price_in_xrd);
-            
-            let dx_token = cdp_data.collateral_token;
-            let dx_amount = cdp_data.collateral_amount;
-            let max_loan_amount = self.get_max_loan_amount(dx_token, dx_amount, borrow_token, borrow_price_in_xrd, collateral_underlying_price_in_xrd, Decimal::ZERO);
-            
-            let mut cdp_avg_rate = Decimal::ZERO;
-            let mut interest = Decimal::ZERO;
-            let mut delta_normalized_amount = Decimal::ZERO;
-            
-            let borrow_bucket: FungibleBucket = if cdp_data.is_stable {
-                let borrow_pool = self.pools.get_mut(&borrow_token).unwrap();
-                interest = borrow_pool.get_stable_interest(cdp_data.borrow_amount, cdp_data.last_update_epoch, cdp_data.stable_rate);
-                let borrow_intent = cdp_data.borrow_amount.checked_add(interest).unwrap().checked_add(amount).unwrap();
-                info!("exist stable: {}:{},{},{}", borrow_token.to_hex(), cdp_data.borrow_amount, interest, borrow_intent);
-                assert_amount(borrow_intent, max_loan_amount);
-                
-                let (_variable_rate, stable_rate, _supply_rate)  = borrow_pool.get_interest_rate(amount);
-                let borrow_bucket = borrow_pool.borrow_stable(amount, stable_rate);
-                cdp_avg_rate = get_weight_rate(cdp_data.borrow_amount.checked_add(interest).unwrap(), cdp_data.stable_rate, amount, stable_rate);
-
-                borrow_bucket
-            }
-            else{
-                let borrow_pool = self.pools.get_mut(&borrow_token).unwrap();
-                let (_, current_borrow_index) = borrow_pool.get_current_index();
-                let exist_borrow = cdp_data.normalized_borrow.checked_mul(current_borrow_index).unwrap();
-                let borrow_intent = exist_borrow.checked_add(amount).unwrap();
-                info!("exist variable: {}:{}*{},{}", borrow_token.to_hex(), cdp_data.normalized_borrow,current_borrow_index, borrow_intent);
-                assert_amount(borrow_intent, max_loan_amount);
-                let (borrow_bucket, normalized_amount) = borrow_pool.borrow_variable(amount);
-                delta_normalized_amount = normalized_amount;
-                borrow_bucket
-            };
-            self.update_cdp_data(cdp_data.is_stable, amount, interest, Decimal::ZERO, delta_normalized_amount, cdp_avg_rate, cdp_id, cdp_data);
-            
+            let borrow_bucket = self.borrow_on_cdp(&cdp_id, borrow_token, amount, is_stable, &price_bundle);
             (borrow_bucket, cdp)
         }
 
-        pub fn withdraw_collateral(&mut self,
-            cdp: NonFungibleBucket,
-            amount: Decimal,
-            price1: String,
-            quote1: ResourceAddress,
-            timestamp1: u64,
-            signature1: String,
-            price2: Option<String>,
-            quote2: Option<ResourceAddress>,
-            timestamp2: Option<u64>,
-            signature2: Option<String>
-        ) -> (FungibleBucket, NonFungibleBucket){
-            let cdp_id = cdp.non_fungible_local_id();
-            let cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
-            let borrow_token = cdp_data.borrow_token;
-            let dx_token = cdp_data.collateral_token;
-            let cdp_id: NonFungibleLocalId = cdp.non_fungible_local_id();
-            let collateral_underlying_token = get_underlying_token_res_addr(cdp_data.collateral_token);
-            let (borrow_price_in_xrd, collateral_underlying_price_in_xrd) = self.get_price_in_xrd(collateral_underlying_token, borrow_token, &price1, quote1, timestamp1, &signature1, price2, quote2, timestamp2, signature2);
-            assert!(borrow_price_in_xrd.is_positive() && collateral_underlying_price_in_xrd.is_positive(), "Incorrect information on price signature.");
+        ///
+        /// Mints a delegation badge, which allows the holder to borrow up to `allowance` of the borrow token
+        /// against the CDP by `extend_borrow_delegated`, while the debt accrues on the CDP.
+        pub fn delegate_credit(&mut self, cdp: NonFungibleBucket, borrow_token: ResourceAddress, allowance: Decimal) -> (NonFungibleBucket, NonFungibleBucket){
             assert_resource(&cdp.resource_address(), &self.cdp_res_mgr.address());
             assert!(cdp.amount() == Decimal::ONE, "Only one CDP can be processed at a time!");
-            
-            
-            let dx_amount = cdp_data.collateral_amount;
-            self.validate_withdraw_collateral(dx_token, dx_amount, borrow_token, borrow_price_in_xrd, collateral_underlying_price_in_xrd, cdp_data.normalized_borrow, amount);
+            assert!(self.pools.contains_key(&borrow_token), "There is no pool of funds corresponding to the assets!");
+            assert!(allowance.is_positive(), "The allowance must be positive!");
+            let cdp_id = get_integer_id(&cdp.non_fungible_local_id());
+            self.delegation_id_counter += 1;
+            let delegation = self.delegation_res_mgr.mint_non_fungible(
+                &NonFungibleLocalId::integer(self.delegation_id_counter),
+                CreditDelegation{
+                    cdp_id,
+                    borrow_token,
+                    allowance
+                }
+            );
+            Runtime::emit_event(DelegateCreditEvent{
+                cdp_id,
+                delegation_id: self.delegation_id_counter,
+                borrow_token,
+                allowance
+            });
+            (cdp, delegation)
+        }
 
-            let divisibility = get_divisibility(dx_token.clone()).unwrap();
-            let underlying_token = get_underlying_token_res_addr(dx_token);
-            let underlying_pool = self.pools.get_mut(&underlying_token).unwrap();
-            let (supply_index, _) = underlying_pool.get_current_index();
+        ///
+        /// Revokes the remaining allowance of a delegation badge of the CDP.
+        pub fn revoke_credit(&mut self, cdp: NonFungibleBucket, delegation_id: u64) -> NonFungibleBucket{
+            assert_resource(&cdp.resource_address(), &self.cdp_res_mgr.address());
+            assert!(cdp.amount() == Decimal::ONE, "Only one CDP can be processed at a time!");
+            let delegation_local_id = NonFungibleLocalId::integer(delegation_id);
+            let delegation_data = self.delegation_res_mgr.get_non_fungible_data::<CreditDelegation>(&delegation_local_id);
+            assert!(
+                cdp.non_fungible_local_id() == NonFungibleLocalId::integer(delegation_data.cdp_id),
+                "The delegation does not belong to the CDP."
+            );
+            self.delegation_res_mgr.update_non_fungible_data(&delegation_local_id, "allowance", Decimal::ZERO);
+            Runtime::emit_event(RevokeCreditEvent{
+                cdp_id: delegation_data.cdp_id,
+                delegation_id,
+                revoked_allowance: delegation_data.allowance
+            });
+            cdp
+        }
+
+        ///
+        /// Borrows against the delegated CDP. The allowance of the delegation badge is consumed,
+        /// the borrowed funds are sent to the delegate and the debt accrues on the CDP of the owner.
+        pub fn extend_borrow_delegated(&mut self,
+            delegation: NonFungibleBucket,
+            amount: Decimal,
+            is_stable: bool,
+            price_bundle: PriceBundle
+        ) -> (FungibleBucket, NonFungibleBucket){
+            assert_resource(&delegation.resource_address(), &self.delegation_res_mgr.address());
+            assert!(delegation.amount() == Decimal::ONE, "Only one delegation can be processed at a time!");
+            let delegation_local_id = delegation.non_fungible_local_id();
+            let delegation_data = self.delegation_res_mgr.get_non_fungible_data::<CreditDelegation>(&delegation_local_id);
+            assert!(amount <= delegation_data.allowance, "The amount borrowed exceeds the allowance of the delegation.");
+            self.delegation_res_mgr.update_non_fungible_data(
+                &delegation_local_id, "allowance", delegation_data.allowance.checked_sub(amount).unwrap()
+            );
+
+            let cdp_id = NonFungibleLocalId::integer(delegation_data.cdp_id);
+            let borrow_bucket = self.borrow_on_cdp(&cdp_id, delegation_data.borrow_token, amount, is_stable, &price_bundle);
+            (borrow_bucket, delegation)
+        }
+
+        fn borrow_on_cdp(&mut self,
+            cdp_id: &NonFungibleLocalId,
+            borrow_token: ResourceAddress,
+            amount: Decimal,
+            is_stable: bool,
+            price_bundle: &PriceBundle
+        ) -> FungibleBucket{
+            assert!(self.pools.contains_key(&borrow_token), "There is no pool of funds corresponding to the assets!");
+            let mut cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(cdp_id);
+            let prices = self.get_prices_in_xrd(self.get_position_tokens(&cdp_data, Some(borrow_token)), price_bundle);
+            self.assert_borrowable(&cdp_data, borrow_token, amount, &prices);
+
+            let mut debt = cdp_data.debts.get(&borrow_token).cloned().unwrap_or(DebtPosition::new(is_stable));
+            if debt.is_empty() {
+                debt.is_stable = is_stable;
+            }
+            assert!(debt.is_stable == is_stable, "The CDP already borrows the asset with another interest rate mode.");
+            self.assert_borrow_cap(borrow_token, amount);
+            self.add_isolated_debt(&cdp_data, borrow_token, amount);
+            assert!(
+                cdp_data.emode_category.is_none() || self.states.get(&borrow_token).unwrap().emode_category == cdp_data.emode_category,
+                "The asset does not belong to the efficiency mode category of the CDP."
+            );
             
-            let take_amount = amount.checked_div(supply_index).unwrap();
-            let dx_bucket = self.collateral_vaults.take_advanced(&dx_token, take_amount, TO_ZERO);
-            let normalized_amount = ceil(amount.checked_div(supply_index).unwrap(), divisibility);
-            let underlying_bucket = underlying_pool.remove_liquity(dx_bucket);
+            let borrow_pool = self.pools.get_mut(&borrow_token).unwrap();
+            let mut borrow_normalized_amount = Decimal::ZERO;
+            let borrow_bucket: FungibleBucket = if is_stable {
+                let interest = borrow_pool.get_stable_interest(debt.borrow_amount, debt.last_update_epoch, debt.stable_rate);
+                let exist_borrow = debt.borrow_amount.checked_add(interest).unwrap();
+                info!("exist stable: {}:{},{},{}", borrow_token.to_hex(), debt.borrow_amount, interest, amount);
+                
+                let (_variable_rate, stable_rate, _supply_rate)  = borrow_pool.get_interest_rate(amount);
+                let borrow_bucket = borrow_pool.borrow_stable(amount, stable_rate);
+                debt.stable_rate = get_weight_rate(exist_borrow, debt.stable_rate, amount, stable_rate);
+                debt.borrow_amount = exist_borrow.checked_add(amount).unwrap();
+                debt.last_update_epoch = borrow_pool.get_accrual_point();
+                borrow_bucket
+            }
+            else{
+                let (borrow_bucket, normalized_amount) = borrow_pool.borrow_variable(amount);
+                info!("exist variable: {}:{}+{}", borrow_token.to_hex(), debt.normalized_borrow, normalized_amount);
+                debt.normalized_borrow = debt.normalized_borrow.checked_add(normalized_amount).unwrap();
+                borrow_normalized_amount = normalized_amount;
+                borrow_bucket
+            };
+            debt.total_borrow = debt.total_borrow.checked_add(amount).unwrap();
+            let stable_rate = debt.stable_rate;
+            cdp_data.debts.insert(borrow_token, debt);
+            self.save_cdp(cdp_id, &cdp_data);
+            Runtime::emit_event(ExtendBorrowEvent{
+                cdp_id: get_integer_id(cdp_id),
+                borrow_token,
+                amount,
+                normalized_amount: borrow_normalized_amount,
+                loan_index: self.get_loan_index(borrow_token),
+                is_stable,
+                stable_rate
+            });
+            
+            borrow_bucket
+        }
+
+        ///
+        /// Opts the CDP into an efficiency mode category (or out of it with `None`).
+        /// All outstanding debts of the CDP must belong to the category, and the CDP must stay healthy
+        /// after the risk parameters changed.
+        pub fn set_cdp_emode(&mut self,
+            cdp: NonFungibleBucket,
+            category_id: Option<u8>,
+            price_bundle: PriceBundle
+        ) -> NonFungibleBucket{
+            assert_resource(&cdp.resource_address(), &self.cdp_res_mgr.address());
+            assert!(cdp.amount() == Decimal::ONE, "Only one CDP can be processed at a time!");
+            let cdp_id = cdp.non_fungible_local_id();
+            let mut cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
+            if let Some(category_id) = category_id {
+                assert!(self.emode_categories.contains_key(&category_id), "Unknown efficiency mode category!");
+                assert!(
+                    cdp_data.debts.iter().all(|(borrow_token, debt)| debt.is_empty() || self.states.get(borrow_token).unwrap().emode_category == Some(category_id)),
+                    "The debts of the CDP do not belong to the efficiency mode category."
+                );
+            }
+
+            cdp_data.emode_category = category_id;
+            if cdp_data.has_debt() {
+                let prices = self.get_prices_in_xrd(self.get_position_tokens(&cdp_data, None), &price_bundle);
+                assert!(!self.get_available_value(&cdp_data, &prices).is_negative(), "Insufficient remaining collateral.");
+            }
+            self.cdp_res_mgr.update_non_fungible_data(&cdp_id, "emode_category", category_id);
+            Runtime::emit_event(SetCdpEModeEvent{
+                cdp_id: get_integer_id(&cdp_id),
+                category_id
+            });
+
+            cdp
+        }
+
+        ///
+        /// Switches the debt of the borrow token between stable rate and variable rate.
+        /// The accrued stable interest is capitalized into the variable loan shares,
+        /// and the variable debt is converted into a stable loan at the current stable rate.
+        pub fn swap_rate_mode(&mut self, cdp: NonFungibleBucket, borrow_token: ResourceAddress) -> NonFungibleBucket{
+            assert_resource(&cdp.resource_address(), &self.cdp_res_mgr.address());
+            assert!(cdp.amount() == Decimal::ONE, "Only one CDP can be processed at a time!");
+            let cdp_id = cdp.non_fungible_local_id();
+            let mut cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
+            assert!(cdp_data.debts.get(&borrow_token).is_some_and(|debt| !debt.is_empty()), "There is no debt of the borrow token in the CDP.");
+
+            let mut debt = cdp_data.debts.get(&borrow_token).cloned().unwrap();
+            let borrow_pool = self.pools.get_mut(&borrow_token).unwrap();
+            if debt.is_stable {
+                let (normalized_amount, interest) = borrow_pool.swap_to_variable(debt.borrow_amount, debt.stable_rate, debt.last_update_epoch);
+                info!("swap to variable: {}:{}+{} ==> {}", borrow_token.to_hex(), debt.borrow_amount, interest, normalized_amount);
+                debt.normalized_borrow = debt.normalized_borrow.checked_add(normalized_amount).unwrap();
+                debt.borrow_amount = Decimal::ZERO;
+                debt.stable_rate = Decimal::ZERO;
+                debt.last_update_epoch = 0u64;
+            }
+            else{
+                let debt_amount = borrow_pool.get_variable_interest(debt.normalized_borrow);
+                let (_variable_rate, stable_rate, _supply_rate) = borrow_pool.get_interest_rate(debt_amount);
+                let loan_amount = borrow_pool.swap_to_stable(debt.normalized_borrow, stable_rate);
+                info!("swap to stable: {}:{} ==> {}@{}", borrow_token.to_hex(), debt.normalized_borrow, loan_amount, stable_rate);
+                debt.normalized_borrow = Decimal::ZERO;
+                debt.borrow_amount = loan_amount;
+                debt.stable_rate = stable_rate;
+                debt.last_update_epoch = borrow_pool.get_accrual_point();
+            }
+            debt.is_stable = !debt.is_stable;
+            let event = SwapRateModeEvent{
+                cdp_id: get_integer_id(&cdp_id),
+                borrow_token,
+                is_stable: debt.is_stable,
+                borrow_amount: debt.borrow_amount,
+                normalized_amount: debt.normalized_borrow,
+                stable_rate: debt.stable_rate
+            };
+            cdp_data.debts.insert(borrow_token, debt);
+            self.save_cdp(&cdp_id, &cdp_data);
+            Runtime::emit_event(event);
+
+            cdp
+        }
+
+        ///
+        /// Resets the stable debt of a CDP to the current stable rate, when the supply rate of the pool
+        /// exceeds the stable rate of the debt multiplied by the rebalance ratio.
+        /// Anyone can call this method to protect the depositors.
+        pub fn rebalance_stable_rate(&mut self, id: u64, borrow_token: ResourceAddress){
+            let cdp_id = NonFungibleLocalId::integer(id);
+            let mut cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
+            assert!(
+                cdp_data.debts.get(&borrow_token).is_some_and(|debt| debt.is_stable && !debt.is_empty()),
+                "There is no stable debt of the borrow token in the CDP."
+            );
+
+            let mut debt = cdp_data.debts.get(&borrow_token).cloned().unwrap();
+            let borrow_pool = self.pools.get_mut(&borrow_token).unwrap();
+            let (_variable_rate, stable_rate, supply_rate) = borrow_pool.get_interest_rate(Decimal::ZERO);
+            info!("rebalance: {}, supply_rate:{}, stable_rate:{}, cdp stable_rate:{}", borrow_token.to_hex(), supply_rate, stable_rate, debt.stable_rate);
+            assert!(
+                supply_rate > debt.stable_rate.checked_mul(self.rebalance_ratio).unwrap() && stable_rate > debt.stable_rate,
+                "The stable rate of the CDP does not meet the rebalance condition."
+            );
+
+            let (loan_amount, _interest) = borrow_pool.rebalance_stable(debt.borrow_amount, debt.stable_rate, debt.last_update_epoch, stable_rate);
+            let previous_rate = debt.stable_rate;
+            debt.borrow_amount = loan_amount;
+            debt.stable_rate = stable_rate;
+            debt.last_update_epoch = borrow_pool.get_accrual_point();
+            cdp_data.debts.insert(borrow_token, debt);
+            self.save_cdp(&cdp_id, &cdp_data);
+            Runtime::emit_event(RebalanceStableRateEvent{
+                cdp_id: id,
+                borrow_token,
+                borrow_amount: loan_amount,
+                previous_rate,
+                stable_rate
+            });
+        }
+
+        ///
+        /// Withdraws collateral from the CDP. The `amount` is denominated in the underlying token for dx tokens,
+        /// LSU and dseXRD are withdrawn as they are.
+        pub fn withdraw_collateral(&mut self,
+            cdp: NonFungibleBucket,
+            dx_token: ResourceAddress,
+            amount: Decimal,
+            price_bundle: PriceBundle
+        ) -> (FungibleBucket, NonFungibleBucket){
+            assert_resource(&cdp.resource_address(), &self.cdp_res_mgr.address());
+            assert!(cdp.amount() == Decimal::ONE, "Only one CDP can be processed at a time!");
+            let cdp_id: NonFungibleLocalId = cdp.non_fungible_local_id();
+            let mut cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
+            assert!(cdp_data.collaterals.contains_key(&dx_token), "the collateral token does not matches CDP.");
+            
+            let (take_amount, normalized_amount) = if self.staking_collaterals.contains_key(&dx_token) {
+                (amount, amount)
+            } else {
+                let divisibility = get_divisibility(dx_token).unwrap();
+                let underlying_token = get_underlying_token_res_addr(dx_token);
+                self.assert_not_paused(underlying_token, Operation::Withdraw);
+                let (supply_index, _) = self.pools.get(&underlying_token).unwrap().get_current_index();
+                let take_amount = amount.checked_div(supply_index).unwrap();
+                (take_amount, ceil(take_amount, divisibility))
+            };
+            cdp_data.remove_collateral(dx_token, normalized_amount);
+            
+            if cdp_data.has_debt() {
+                let prices = self.get_prices_in_xrd(self.get_position_tokens(&cdp_data, None), &price_bundle);
+                let available_value = self.get_available_value(&cdp_data, &prices);
+                info!("available_value:{}, withdraw_amount:{}", available_value, amount);
+                assert!(!available_value.is_negative(), "Insufficient remaining collateral.");
+            }
+
+            let underlying_bucket = self.release_collateral(dx_token, take_amount, false);
             info!("amount:{}, take_amount:{}, normalized_amount:{}, underlying_bucket.amount:{}",amount, take_amount, normalized_amount, underlying_bucket.amount());
-            self.cdp_res_mgr.update_non_fungible_data(&cdp_id, "collateral_amount", dx_amount.checked_sub(normalized_amount).unwrap());
+            self.save_cdp(&cdp_id, &cdp_data);
+            Runtime::emit_event(WithdrawCollateralEvent{
+                cdp_id: get_integer_id(&cdp_id),
+                collateral_token: dx_token,
+                amount: normalized_amount,
+                withdraw_token: underlying_bucket.resource_address(),
+                withdraw_amount: underlying_bucket.amount()
+            });
             (underlying_bucket, cdp)
         }
-        
-        fn validate_withdraw_collateral(&self, dx_token: ResourceAddress, dx_amount: Decimal,
-            borrow_token: ResourceAddress, borrow_price_in_xrd: Decimal, 
-            collateral_underlying_price_in_xrd: Decimal, cdp_normalized_borrow: Decimal,
-            withdraw_amount: Decimal
-        ){
-            let max_loan_amount = self.get_max_loan_amount(dx_token, dx_amount, borrow_token, borrow_price_in_xrd, collateral_underlying_price_in_xrd, withdraw_amount);
-            let borrow_pool = self.pools.get(&borrow_token).unwrap();
-            let (_supply_index, borrow_index) = borrow_pool.get_current_index();
-            let current_borrow_amount = cdp_normalized_borrow.checked_mul(borrow_index).unwrap();
-            info!("current_borrow_amount:{}, max_loan_amount:{}, withdraw_amount:{}",  current_borrow_amount, max_loan_amount, withdraw_amount);
-            assert!(max_loan_amount >= current_borrow_amount, "Insufficient remaining collateral.");
-        }
 
-        pub fn addition_collateral(&mut self, id: u64, bucket: FungibleBucket){
+        ///
+        /// Tops up the collateral of a CDP, anyone can add more of a collateral the CDP already holds.
+        /// A collateral type new to the CDP requires the proof of the CDP.
+        pub fn addition_collateral(&mut self, id: u64, bucket: FungibleBucket, cdp_proof: Option<Proof>){
             let cdp_id = NonFungibleLocalId::integer(id);
-            let cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
-            let dx_token = cdp_data.collateral_token;
+            let mut cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
             
-            let dx_bucket = self.get_dx_bucket(cdp_data.collateral_token, dx_token, bucket);
+            let dx_bucket = self.get_dx_bucket(bucket);
+            let dx_token = dx_bucket.resource_address();
             let dx_amount = dx_bucket.amount();
+            if !cdp_data.collaterals.contains_key(&dx_token) {
+                self.assert_cdp_proof(&cdp_id, cdp_proof);
+            }
+            self.assert_isolation_collateral(&cdp_data, dx_token);
+            cdp_data.add_collateral(dx_token, dx_amount);
             self.collateral_vaults.put(dx_bucket);
-            self.update_cdp_data(cdp_data.is_stable, Decimal::ZERO, Decimal::ZERO, dx_amount,  Decimal::ZERO, Decimal::ZERO, cdp_id, cdp_data);
-
+            self.save_cdp(&cdp_id, &cdp_data);
+            Runtime::emit_event(AddCollateralEvent{
+                cdp_id: id,
+                collateral_token: dx_token,
+                amount: dx_amount
+            });
         }
 
-        fn get_dx_bucket(&mut self, collateral_token: ResourceAddress, dx_token: ResourceAddress, bucket: FungibleBucket)-> FungibleBucket{
+        fn get_dx_bucket(&mut self, bucket: FungibleBucket)-> FungibleBucket{
             let bucket_token = bucket.resource_address();
-            let underlying_token = get_underlying_token_res_addr(dx_token);
-            assert!(collateral_token == bucket_token || underlying_token == bucket_token , "The addition of collateralized asset must match the current CDP collateral asset.");
-
-            if bucket_token == collateral_token {
-                bucket
-            } else{
-                let underlying_pool = self.pools.get_mut(&underlying_token).unwrap();
+            if self.pools.contains_key(&bucket_token) {
+                self.assert_supply_cap(bucket_token, bucket.amount());
+                let underlying_pool = self.pools.get_mut(&bucket_token).unwrap();
                 underlying_pool.add_liquity(bucket)
+            } else{
+                self.assert_collateral_token(bucket_token);
+                bucket
             }
         }
 
+        fn assert_cdp_proof(&self, cdp_id: &NonFungibleLocalId, cdp_proof: Option<Proof>){
+            let proof = cdp_proof.expect("The proof of the CDP is required to add a new collateral type.");
+            let checked_proof = proof.check_with_message(self.cdp_res_mgr.address(), "The proof is not the CDP!");
+            assert!(
+                checked_proof.as_non_fungible().non_fungible_local_id() == *cdp_id,
+                "The proof does not match the CDP!"
+            );
+        }
+
+        fn assert_collateral_token(&self, dx_token: ResourceAddress){
+            assert!(
+                self.staking_collaterals.contains_key(&dx_token) || self.states.values().any(|state| state.collateral_token == dx_token),
+                "The collateral asset is not supported!"
+            );
+        }
+
         pub fn repay(&mut self, repay_bucket: FungibleBucket, id: u64) -> (FungibleBucket, Decimal){
             let cdp_id: NonFungibleLocalId = NonFungibleLocalId::integer(id);
-            let cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
-            let borrow_token = cdp_data.borrow_token;
-            assert_resource(&borrow_token, &repay_bucket.resource_address());
+            let mut cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
+            let borrow_token = repay_bucket.resource_address();
+            assert!(cdp_data.debts.contains_key(&borrow_token), "the borrow token does not matches CDP.");
             
-            let (bucket, payment_amount) = if cdp_data.is_stable {
-                let (return_bucket, actual_repay_amount, repay_in_borrow) = self.repay_stable_to_pool(borrow_token, repay_bucket, cdp_data.borrow_amount, cdp_data.stable_rate, cdp_data.last_update_epoch, None);
-                self.update_cdp_after_repay(&cdp_id, cdp_data, actual_repay_amount, repay_in_borrow, Decimal::ZERO, Decimal::ZERO);
-                (return_bucket, actual_repay_amount)
+            let (bucket, payment_amount) = self.repay_debt(id, &mut cdp_data, borrow_token, repay_bucket, None);
+            self.save_cdp(&cdp_id, &cdp_data);
+
+            (bucket, payment_amount)
+        }
+
+        ///
+        /// Settles all debts of the CDP, releases every remaining unit of collateral and burns the CDP.
+        ///
+        /// # Arguments
+        /// * `cdp`: The CDP to close.
+        /// * `repay_buckets`: Buckets of the borrowed tokens, they must cover all the debts (including accrued interest).
+        /// * `as_dx_token`: Whether the collaterals are returned as dx tokens instead of the underlying tokens. LSU and dseXRD are always returned as they are.
+        ///
+        /// # Returns
+        ///
+        /// The released collaterals and the overpayment of the repay buckets.
+        pub fn close_cdp(&mut self, cdp: NonFungibleBucket, repay_buckets: Vec<FungibleBucket>, as_dx_token: bool) -> Vec<Bucket>{
+            assert_resource(&cdp.resource_address(), &self.cdp_res_mgr.address());
+            assert!(cdp.amount() == Decimal::ONE, "Only one CDP can be processed at a time!");
+            let cdp_id = cdp.non_fungible_local_id();
+            let id = get_integer_id(&cdp_id);
+            let mut cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
+
+            let mut buckets: Vec<Bucket> = Vec::new();
+            for repay_bucket in repay_buckets {
+                let borrow_token = repay_bucket.resource_address();
+                let return_bucket = if cdp_data.debts.get(&borrow_token).is_some_and(|debt| !debt.is_empty()) {
+                    let (return_bucket, _) = self.repay_debt(id, &mut cdp_data, borrow_token, repay_bucket, None);
+                    return_bucket
+                } else {
+                    repay_bucket
+                };
+                buckets.push(return_bucket.into());
             }
-            else{
-                let (return_bucket, actual_repay_amount, repay_normalized_amount) = self.repay_variable_to_pool(borrow_token, repay_bucket, cdp_data.normalized_borrow, None);
-                self.update_cdp_after_repay(&cdp_id, cdp_data, actual_repay_amount, Decimal::ZERO, repay_normalized_amount, Decimal::ZERO);
-                (return_bucket, actual_repay_amount)
+            assert!(!cdp_data.has_debt(), "The repay buckets do not cover all debts of the CDP.");
+
+            for (dx_token, dx_amount) in cdp_data.collaterals.iter() {
+                let collateral_bucket = self.release_collateral(*dx_token, *dx_amount, as_dx_token);
+                info!("close cdp, collateral {}: {}|{}", Runtime::bech32_encode_address(*dx_token), dx_amount, collateral_bucket.amount());
+                Runtime::emit_event(WithdrawCollateralEvent{
+                    cdp_id: id,
+                    collateral_token: *dx_token,
+                    amount: *dx_amount,
+                    withdraw_token: collateral_bucket.resource_address(),
+                    withdraw_amount: collateral_bucket.amount()
+                });
+                buckets.push(collateral_bucket.into());
+            }
+
+            self.cdp_res_mgr.burn(cdp);
+            Runtime::emit_event(CloseCdpEvent{
+                cdp_id: id
+            });
+            buckets
+        }
+
+        ///
+        /// Combines several CDPs into the first one, the others are burned.
+        /// The debts of the same borrow token must use the same interest rate mode,
+        /// the stable debts are capitalized and re-weighted to a single stable rate.
+        pub fn merge_cdps(&mut self, cdps: Vec<NonFungibleBucket>, price_bundle: PriceBundle) -> NonFungibleBucket{
+            assert!(cdps.len() > 1, "At least two CDPs are required to merge!");
+            let mut cdp_iter = cdps.into_iter();
+            let cdp = cdp_iter.next().unwrap();
+            assert_resource(&cdp.resource_address(), &self.cdp_res_mgr.address());
+            assert!(cdp.amount() == Decimal::ONE, "Only one CDP can be processed at a time!");
+            let cdp_id = cdp.non_fungible_local_id();
+            let mut cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
+            let mut merged_cdp_ids: Vec<u64> = Vec::new();
+
+            for other_cdp in cdp_iter {
+                assert_resource(&other_cdp.resource_address(), &self.cdp_res_mgr.address());
+                let other_ids = other_cdp.non_fungible_local_ids();
+                for other_id in other_ids.iter() {
+                    merged_cdp_ids.push(get_integer_id(other_id));
+                    let other_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(other_id);
+                    assert!(other_data.emode_category == cdp_data.emode_category, "The CDPs belong to different efficiency mode categories.");
+                    for (collateral_token, amount) in other_data.collaterals.iter() {
+                        self.assert_isolation_collateral(&cdp_data, *collateral_token);
+                        cdp_data.add_collateral(*collateral_token, *amount);
+                    }
+                    for (borrow_token, other_debt) in other_data.debts.iter() {
+                        let merged_debt = self.merge_debt(*borrow_token, cdp_data.debts.get(borrow_token).cloned(), other_debt.clone());
+                        cdp_data.debts.insert(*borrow_token, merged_debt);
+                    }
+                }
+                self.cdp_res_mgr.burn(other_cdp);
+            }
+
+            if cdp_data.has_debt() {
+                let prices = self.get_prices_in_xrd(self.get_position_tokens(&cdp_data, None), &price_bundle);
+                assert!(!self.get_available_value(&cdp_data, &prices).is_negative(), "Insufficient collateral of the merged CDP.");
+            }
+            self.save_cdp(&cdp_id, &cdp_data);
+            Runtime::emit_event(MergeCdpsEvent{
+                cdp_id: get_integer_id(&cdp_id),
+                merged_cdp_ids
+            });
+            cdp
+        }
+
+        fn merge_debt(&mut self, borrow_token: ResourceAddress, debt: Option<DebtPosition>, other_debt: DebtPosition) -> DebtPosition{
+            let mut debt = match debt {
+                Some(debt) => debt,
+                None => return other_debt
             };
+            if other_debt.is_empty() || debt.is_empty() {
+                let mut merged_debt = if debt.is_empty() { other_debt.clone() } else { debt.clone() };
+                merged_debt.total_borrow = debt.total_borrow.checked_add(other_debt.total_borrow).unwrap();
+                merged_debt.total_repay = debt.total_repay.checked_add(other_debt.total_repay).unwrap();
+                return merged_debt;
+            }
+            assert!(debt.is_stable == other_debt.is_stable, "The debts of the borrow token use different interest rate modes.");
 
-            (bucket, payment_amount)
+            if debt.is_stable {
+                let borrow_pool = self.pools.get_mut(&borrow_token).unwrap();
+                // capitalize the accrued interest at the original rates, then re-weight the stable rate.
+                let (loan_amount, _) = borrow_pool.rebalance_stable(debt.borrow_amount, debt.stable_rate, debt.last_update_epoch, debt.stable_rate);
+                let (other_loan_amount, _) = borrow_pool.rebalance_stable(other_debt.borrow_amount, other_debt.stable_rate, other_debt.last_update_epoch, other_debt.stable_rate);
+                debt.stable_rate = get_weight_rate(loan_amount, debt.stable_rate, other_loan_amount, other_debt.stable_rate);
+                debt.borrow_amount = loan_amount.checked_add(other_loan_amount).unwrap();
+                debt.last_update_epoch = borrow_pool.get_accrual_point();
+            } else {
+                debt.normalized_borrow = debt.normalized_borrow.checked_add(other_debt.normalized_borrow).unwrap();
+            }
+            debt.total_borrow = debt.total_borrow.checked_add(other_debt.total_borrow).unwrap();
+            debt.total_repay = debt.total_repay.checked_add(other_debt.total_repay).unwrap();
+            debt
+        }
+
+        ///
+        /// Moves a proportional slice (`fraction`) of every collateral and debt of the CDP into a new CDP.
+        ///
+        /// # Returns
+        ///
+        /// The original CDP and the new CDP.
+        pub fn split_cdp(&mut self, cdp: NonFungibleBucket, fraction: Decimal, price_bundle: PriceBundle) -> (NonFungibleBucket, NonFungibleBucket){
+            assert_resource(&cdp.resource_address(), &self.cdp_res_mgr.address());
+            assert!(cdp.amount() == Decimal::ONE, "Only one CDP can be processed at a time!");
+            assert!(fraction.is_positive() && fraction < Decimal::ONE, "The fraction must be between zero and one!");
+            let cdp_id = cdp.non_fungible_local_id();
+            let mut cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
+            let mut new_cdp_data = CollateralDebtPosition{
+                collaterals: HashMap::new(),
+                debts: HashMap::new(),
+                emode_category: cdp_data.emode_category
+            };
+
+            for (collateral_token, amount) in cdp_data.collaterals.clone().iter() {
+                let split_amount = floor_by_resource(*collateral_token, amount.checked_mul(fraction).unwrap());
+                if split_amount.is_positive() {
+                    cdp_data.remove_collateral(*collateral_token, split_amount);
+                    new_cdp_data.add_collateral(*collateral_token, split_amount);
+                }
+            }
+            for (borrow_token, debt) in cdp_data.debts.iter_mut() {
+                if debt.is_empty() {
+                    continue;
+                }
+                let mut new_debt = DebtPosition::new(debt.is_stable);
+                if debt.is_stable {
+                    new_debt.borrow_amount = debt.borrow_amount.checked_mul(fraction).unwrap();
+                    new_debt.stable_rate = debt.stable_rate;
+                    new_debt.last_update_epoch = debt.last_update_epoch;
+                    debt.borrow_amount = debt.borrow_amount.checked_sub(new_debt.borrow_amount).unwrap();
+                } else {
+                    new_debt.normalized_borrow = debt.normalized_borrow.checked_mul(fraction).unwrap();
+                    debt.normalized_borrow = debt.normalized_borrow.checked_sub(new_debt.normalized_borrow).unwrap();
+                }
+                new_debt.total_borrow = debt.total_borrow.checked_mul(fraction).unwrap();
+                new_debt.total_repay = debt.total_repay.checked_mul(fraction).unwrap();
+                debt.total_borrow = debt.total_borrow.checked_sub(new_debt.total_borrow).unwrap();
+                debt.total_repay = debt.total_repay.checked_sub(new_debt.total_repay).unwrap();
+                new_cdp_data.debts.insert(*borrow_token, new_debt);
+            }
+
+            if cdp_data.has_debt() || new_cdp_data.has_debt() {
+                let mut tokens = self.get_position_tokens(&cdp_data, None);
+                tokens.extend(self.get_position_tokens(&new_cdp_data, None));
+                let prices = self.get_prices_in_xrd(tokens, &price_bundle);
+                assert!(!self.get_available_value(&cdp_data, &prices).is_negative(), "Insufficient collateral of the original CDP.");
+                assert!(!self.get_available_value(&new_cdp_data, &prices).is_negative(), "Insufficient collateral of the new CDP.");
+            }
+            self.save_cdp(&cdp_id, &cdp_data);
+            let new_cdp = self.new_cdp(new_cdp_data);
+            Runtime::emit_event(SplitCdpEvent{
+                cdp_id: get_integer_id(&cdp_id),
+                new_cdp_id: self.cdp_id_counter,
+                fraction
+            });
+            (cdp, new_cdp)
+        }
+
+        fn repay_debt(&mut self, 
+            cdp_id: u64,
+            cdp_data: &mut CollateralDebtPosition,
+            borrow_token: ResourceAddress,
+            repay_bucket: FungibleBucket,
+            repay_opt: Option<Decimal>
+        ) -> (FungibleBucket, Decimal){
+            let mut debt = cdp_data.debts.get(&borrow_token).cloned().unwrap();
+            let (return_bucket, actual_repay_amount, repay_normalized_amount) = if debt.is_stable {
+                let (return_bucket, actual_repay_amount, repay_in_borrow) = self.repay_stable_to_pool(borrow_token, repay_bucket, debt.borrow_amount, debt.stable_rate, debt.last_update_epoch, repay_opt);
+                info!("borrow_amount:{}-{}", debt.borrow_amount, repay_in_borrow);
+                debt.borrow_amount = debt.borrow_amount.checked_sub(repay_in_borrow).unwrap();
+                if debt.borrow_amount == Decimal::ZERO {
+                    debt.stable_rate = Decimal::ZERO;
+                }
+                debt.last_update_epoch = self.get_accrual_point(borrow_token);
+                (return_bucket, actual_repay_amount, Decimal::ZERO)
+            }
+            else{
+                let (return_bucket, actual_repay_amount, repay_normalized_amount) = self.repay_variable_to_pool(borrow_token, repay_bucket, debt.normalized_borrow, repay_opt);
+                info!("normalized_borrow:{}-{}", debt.normalized_borrow, repay_normalized_amount);
+                debt.normalized_borrow = debt.normalized_borrow.checked_sub(repay_normalized_amount).unwrap();
+                (return_bucket, actual_repay_amount, repay_normalized_amount)
+            };
+            // the repayment settles the principal first, the excess over the total borrowed is interest.
+            let outstanding_principal = debt.total_borrow.checked_sub(debt.total_repay).unwrap();
+            let principal_repay = if outstanding_principal <= Decimal::ZERO { Decimal::ZERO }
+                else if actual_repay_amount > outstanding_principal { outstanding_principal }
+                else { actual_repay_amount };
+            info!("total_repay:{}+{}", debt.total_repay, actual_repay_amount);
+            debt.total_repay = debt.total_repay.checked_add(actual_repay_amount).unwrap();
+            let is_stable = debt.is_stable;
+            cdp_data.debts.insert(borrow_token, debt);
+            self.reduce_isolated_debt(cdp_data, principal_repay);
+            Runtime::emit_event(RepayEvent{
+                cdp_id,
+                borrow_token,
+                amount: actual_repay_amount,
+                normalized_amount: repay_normalized_amount,
+                loan_index: self.get_loan_index(borrow_token),
+                is_stable
+            });
+            (return_bucket, actual_repay_amount)
         }
 
         fn repay_variable_to_pool(&mut self, borrow_token: ResourceAddress, repay_bucket: FungibleBucket, cdp_normalized_borrow: Decimal, repay_opt: Option<Decimal>) -> (FungibleBucket, Decimal, Decimal){
@@ -516,66 +1632,124 @@ mod cdp_mgr{
             debt_bucket: FungibleBucket,
             debt_to_cover: Decimal,
             id: u64,
-            price1: String,
-            quote1: ResourceAddress,
-            timestamp1: u64,
-            signature1: String,
-            price2: Option<String>,
-            quote2: Option<ResourceAddress>,
-            timestamp2: Option<u64>,
-            signature2: Option<String>
+            collateral_token: ResourceAddress,
+            price_bundle: PriceBundle
         ) -> (FungibleBucket, FungibleBucket){
             let cdp_id = NonFungibleLocalId::integer(id);
             let cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
-            let borrow_token = cdp_data.borrow_token;
-            let dx_token = cdp_data.collateral_token;
-            let underlying_token = get_underlying_token_res_addr(dx_token);
-            let dx_amount = cdp_data.collateral_amount;
-            assert!(borrow_token == debt_bucket.resource_address(), "the borrow token does not matches CDP.");
+            let borrow_token = debt_bucket.resource_address();
+            assert!(cdp_data.debts.contains_key(&borrow_token), "the borrow token does not matches CDP.");
+            assert!(cdp_data.collaterals.contains_key(&collateral_token), "the collateral token does not matches CDP.");
+
+            let prices = self.get_prices_in_xrd(self.get_position_tokens(&cdp_data, None), &price_bundle);
+            self.liquidate(id, cdp_data, debt_bucket, debt_to_cover, collateral_token, &prices)
+        }
+
+        ///
+        /// Liquidates the CDP without holding the debt asset in advance.
+        /// The debt to liquidate is advanced from the borrow pool, and the seized collateral is handed out together with
+        /// a transient FlashLoanData NFT, which must be settled by `repay_flashloan` with the debt asset before the transaction ends.
+        /// So the liquidator can swap the collateral for the debt asset in-between.
+        ///
+        /// # Returns
+        ///
+        /// The seized collateral, the remainder of the advance and the transient receipt.
+        pub fn flash_liquidation(&mut self,
+            borrow_token: ResourceAddress,
+            debt_to_cover: Decimal,
+            id: u64,
+            collateral_token: ResourceAddress,
+            price_bundle: PriceBundle,
+            fee_exempt: Option<Proof>
+        ) -> (FungibleBucket, FungibleBucket, NonFungibleBucket){
+            let cdp_id = NonFungibleLocalId::integer(id);
+            let cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
+            assert!(cdp_data.debts.contains_key(&borrow_token), "the borrow token does not matches CDP.");
+            assert!(cdp_data.collaterals.contains_key(&collateral_token), "the collateral token does not matches CDP.");
+
+            let prices = self.get_prices_in_xrd(self.get_position_tokens(&cdp_data, None), &price_bundle);
+            let (actual_debt_to_liquidate, _) = self.get_liquidate_debt_and_collateral(
+                &cdp_data, &prices, debt_to_cover, borrow_token, collateral_token
+            );
+            self.assert_not_paused(borrow_token, Operation::Liquidation);
+            self.assert_not_paused(borrow_token, Operation::FlashLoan);
+            let advance_amount = ceil_by_resource(borrow_token, actual_debt_to_liquidate);
+            self.assert_borrow_cap(borrow_token, advance_amount);
+            let fee_ratio = self.get_flashloan_fee_ratio(borrow_token, fee_exempt);
+            let pool = self.pools.get_mut(&borrow_token).unwrap();
+            let debt_bucket = pool.borrow_fixed_term(advance_amount);
+            let fee = debt_bucket.amount().checked_mul(fee_ratio).unwrap();
+            self.transient_id_counter += 1;
+            let data = FlashLoanData{
+                amount: debt_bucket.amount(),
+                res_addr: borrow_token,
+                fee
+            };
+            let flashloan_nft = self.transient_nft_res_mgr.mint_non_fungible::<FlashLoanData>(&NonFungibleLocalId::integer(self.transient_id_counter), data);
+            Runtime::emit_event(FlashLoanBorrowEvent{
+                flashloan_id: self.transient_id_counter,
+                res_addr: borrow_token,
+                amount: debt_bucket.amount(),
+                fee
+            });
 
-            let (borrow_price_in_xrd, collateral_underlying_price_in_xrd) = self.get_price_in_xrd(underlying_token, borrow_token, &price1, quote1, timestamp1, &signature1, price2, quote2, timestamp2, signature2);
-            assert!(borrow_price_in_xrd.is_positive() || collateral_underlying_price_in_xrd.is_positive(), "Incorrect information on price signature.");
+            let (collateral_bucket, remainder_bucket) = self.liquidate(id, cdp_data, debt_bucket, actual_debt_to_liquidate, collateral_token, &prices);
+            (collateral_bucket, remainder_bucket, flashloan_nft)
+        }
 
+        fn liquidate(&mut self,
+            id: u64,
+            mut cdp_data: CollateralDebtPosition,
+            debt_bucket: FungibleBucket,
+            debt_to_cover: Decimal,
+            collateral_token: ResourceAddress,
+            prices: &HashMap<ResourceAddress, Decimal>
+        ) -> (FungibleBucket, FungibleBucket){
+            let cdp_id = NonFungibleLocalId::integer(id);
+            let borrow_token = debt_bucket.resource_address();
+            self.assert_not_paused(borrow_token, Operation::Liquidation);
+            let isolated_token = self.get_isolated_collateral(&cdp_data);
             let (actual_debt_to_liquidate,release_collateral_to_liqiudate) = self.get_liquidate_debt_and_collateral(
-                borrow_price_in_xrd, collateral_underlying_price_in_xrd, debt_to_cover,
-                borrow_token, underlying_token.clone(), cdp_data.borrow_amount, cdp_data.normalized_borrow, cdp_data.collateral_amount, cdp_data.is_stable,
-                cdp_data.stable_rate, cdp_data.last_update_epoch
+                &cdp_data, prices, debt_to_cover, borrow_token, collateral_token
             );
             info!("actual_debt_to_liquidate:{}, release_collateral_to_liqiudate:{}", actual_debt_to_liquidate, release_collateral_to_liqiudate);
 
             let repay_amount = debt_bucket.amount();
             assert!(repay_amount >= actual_debt_to_liquidate, "the debt bucket does not cover to debt of the CDP.");
             
-            let (bucket, actual_repay_amount) = if cdp_data.is_stable{
-                let (return_bucket, actual_repay_amount, repay_in_borrow) = self.repay_stable_to_pool(borrow_token, debt_bucket, cdp_data.borrow_amount, cdp_data.stable_rate, cdp_data.last_update_epoch, Some(actual_debt_to_liquidate));
-                info!("stable debt: actual_repay_amount:{}, bucket:{}", actual_repay_amount, return_bucket.amount());
-                self.update_cdp_after_repay(&cdp_id, cdp_data, actual_repay_amount, repay_in_borrow, Decimal::ZERO, Decimal::ZERO);
-                (return_bucket, actual_repay_amount)
-            }
-            else{
-                let (return_bucket, actual_repay_amount, repay_normalized_amount) = self.repay_variable_to_pool(borrow_token, debt_bucket, cdp_data.normalized_borrow, Some(actual_debt_to_liquidate));
-                info!("variable debt: repay_normalized_amount:{}, bucket:{}, actual_repay_amount:{}", repay_normalized_amount, return_bucket.amount(), actual_repay_amount);
-                self.update_cdp_after_repay(&cdp_id, cdp_data, actual_debt_to_liquidate, Decimal::ZERO, repay_normalized_amount, Decimal::ZERO);
-                (return_bucket, actual_repay_amount)
-            };
+            let (bucket, actual_repay_amount) = self.repay_debt(id, &mut cdp_data, borrow_token, debt_bucket, Some(actual_debt_to_liquidate));
             assert!(actual_repay_amount == actual_debt_to_liquidate, "The actual repay amount dose not matches debt to liquidate.");
 
             info!("debt_bucket:{}", bucket.amount());
-            let underlying_pool = self.pools.get_mut(&underlying_token).unwrap();
-            // let mut vault = self.collateral_vaults.get_mut(&dx_token).unwrap();
-            info!("underlying:{}, dx:{}, dx_vault:{}", Runtime::bech32_encode_address(underlying_token), Runtime::bech32_encode_address(dx_token.clone()),self.collateral_vaults.amount(&dx_token));
-            let release_underlying_bucket = underlying_pool.remove_liquity(self.collateral_vaults.take_advanced(&dx_token, release_collateral_to_liqiudate, TO_ZERO));
+            info!("collateral:{}, collateral_vault:{}", Runtime::bech32_encode_address(collateral_token), self.collateral_vaults.amount(&collateral_token));
+            let release_underlying_bucket = self.release_collateral(collateral_token, release_collateral_to_liqiudate, false);
             info!("underlying(collateral) amount:{}", release_underlying_bucket.amount());
-            self.cdp_res_mgr.update_non_fungible_data(&cdp_id, "collateral_amount", dx_amount.checked_sub(release_collateral_to_liqiudate).unwrap());
+            cdp_data.remove_collateral(collateral_token, release_collateral_to_liqiudate);
+            Runtime::emit_event(LiquidationEvent{
+                cdp_id: id,
+                borrow_token,
+                debt_amount: actual_repay_amount,
+                collateral_token,
+                collateral_amount: release_collateral_to_liqiudate,
+                withdraw_token: release_underlying_bucket.resource_address(),
+                withdraw_amount: release_underlying_bucket.amount()
+            });
+            if cdp_data.collaterals.is_empty() && cdp_data.has_debt() {
+                self.write_off_bad_debts(id, &mut cdp_data, isolated_token);
+            }
+            self.save_cdp(&cdp_id, &cdp_data);
             (release_underlying_bucket, bucket)
 
         }
 
-        pub fn borrow_flashloan(&mut self, res_addr: ResourceAddress, amount: Decimal) -> (FungibleBucket, NonFungibleBucket){
+        pub fn borrow_flashloan(&mut self, res_addr: ResourceAddress, amount: Decimal, fee_exempt: Option<Proof>) -> (FungibleBucket, NonFungibleBucket){
             assert!(self.pools.get(&res_addr).is_some(), "unknow token resource address.");
+            self.assert_not_paused(res_addr, Operation::FlashLoan);
+            self.assert_borrow_cap(res_addr, amount);
+            let fee_ratio = self.get_flashloan_fee_ratio(res_addr, fee_exempt);
             let pool = self.pools.get_mut(&res_addr).unwrap();
             let bucket = pool.borrow_fixed_term(amount);
-            let fee = bucket.amount().checked_mul(pool.get_flashloan_fee_ratio()).unwrap();
+            let fee = bucket.amount().checked_mul(fee_ratio).unwrap();
             self.transient_id_counter += 1;
             let data = FlashLoanData{
                 amount: bucket.amount(),
@@ -583,6 +1757,12 @@ mod cdp_mgr{
                 fee
             };
             let flashloan_nft = self.transient_nft_res_mgr.mint_non_fungible::<FlashLoanData>(&NonFungibleLocalId::integer(self.transient_id_counter), data);
+            Runtime::emit_event(FlashLoanBorrowEvent{
+                flashloan_id: self.transient_id_counter,
+                res_addr,
+                amount: bucket.amount(),
+                fee
+            });
             (bucket, flashloan_nft)
         }
 
@@ -599,14 +1779,150 @@ mod cdp_mgr{
                 );
             
             self.transient_nft_res_mgr.burn(flashloan);
+            Runtime::emit_event(FlashLoanRepayEvent{
+                flashloan_id: get_integer_id(&flashloan_id),
+                res_addr: underlying,
+                amount: flashloan_data.amount,
+                fee: flashloan_data.fee
+            });
             let pool = self.pools.get_mut(&underlying).unwrap();
             pool.repay_fixed_term(repay_bucket, flashloan_data.amount, flashloan_data.fee)
         }
 
-        pub fn withdraw_insurance(&mut self, underlying_token_addr: ResourceAddress, amount: Decimal) -> FungibleBucket{
+        ///
+        /// Borrows several assets in one flash loan, every leg is listed in a single transient NFT.
+        ///
+        /// # Arguments
+        /// * `loans`: The (underlying token, amount) of each leg, each asset can appear only once.
+        /// * `fee_exempt`: Proof of the fee exempt badge, the flash loan is free of fee with it.
+        ///
+        /// # Returns
+        ///
+        /// The borrowed buckets in the order of the legs and the transient NFT.
+        pub fn borrow_flashloan_batch(&mut self, loans: Vec<(ResourceAddress, Decimal)>, fee_exempt: Option<Proof>) -> (Vec<FungibleBucket>, NonFungibleBucket){
+            assert!(!loans.is_empty(), "There is no loan in the flash loan batch.");
+            let is_fee_exempt = self.is_fee_exempt(fee_exempt);
+            let mut buckets: Vec<FungibleBucket> = Vec::new();
+            let mut legs: Vec<FlashLoanLeg> = Vec::new();
+            self.transient_id_counter += 1;
+            for (res_addr, amount) in loans {
+                assert!(self.pools.get(&res_addr).is_some(), "unknow token resource address.");
+                assert!(!legs.iter().any(|leg| leg.res_addr == res_addr), "Duplicate asset in the flash loan batch.");
+                self.assert_not_paused(res_addr, Operation::FlashLoan);
+                self.assert_borrow_cap(res_addr, amount);
+                let pool = self.pools.get_mut(&res_addr).unwrap();
+                let bucket = pool.borrow_fixed_term(amount);
+                let fee = if is_fee_exempt { Decimal::ZERO } else { bucket.amount().checked_mul(pool.get_flashloan_fee_ratio()).unwrap() };
+                Runtime::emit_event(FlashLoanBorrowEvent{
+                    flashloan_id: self.transient_id_counter,
+                    res_addr,
+                    amount: bucket.amount(),
+                    fee
+                });
+                legs.push(FlashLoanLeg{
+                    res_addr,
+                    amount: bucket.amount(),
+                    fee
+                });
+                buckets.push(bucket);
+            }
+
+            let flashloan_nft = self.batch_transient_nft_res_mgr.mint_non_fungible::<FlashLoanBatchData>(
+                &NonFungibleLocalId::integer(self.transient_id_counter), FlashLoanBatchData{ legs }
+            );
+            (buckets, flashloan_nft)
+        }
+
+        ///
+        /// Settles every leg of the batch flash loan, the repay buckets can be given in any order.
+        /// Returns the remainders of the repay buckets.
+        pub fn repay_flashloan_batch(&mut self, repay_buckets: Vec<FungibleBucket>, flashloan: NonFungibleBucket) -> Vec<FungibleBucket>{
+            assert_resource(&flashloan.resource_address(), &self.batch_transient_nft_res_mgr.address());
+            let flashloan_id : NonFungibleLocalId = flashloan.non_fungible_local_id();
+            let flashloan_data = self.batch_transient_nft_res_mgr.get_non_fungible_data::<FlashLoanBatchData>(&flashloan_id);
+
+            let mut bucket_map: HashMap<ResourceAddress, FungibleBucket> = HashMap::new();
+            for bucket in repay_buckets {
+                let res_addr = bucket.resource_address();
+                if let Some(existing_bucket) = bucket_map.get_mut(&res_addr) {
+                    existing_bucket.put(bucket);
+                } else {
+                    bucket_map.insert(res_addr, bucket);
+                }
+            }
+
+            for leg in flashloan_data.legs {
+                let repay_bucket = bucket_map.remove(&leg.res_addr).expect("Missing repayment of a flash loan leg.");
+                assert!(
+                    repay_bucket.amount() >= leg.amount.checked_add(leg.fee).unwrap(),
+                    "Insufficient repay amount of the flash loan leg {}!", Runtime::bech32_encode_address(leg.res_addr)
+                );
+                Runtime::emit_event(FlashLoanRepayEvent{
+                    flashloan_id: get_integer_id(&flashloan_id),
+                    res_addr: leg.res_addr,
+                    amount: leg.amount,
+                    fee: leg.fee
+                });
+                let pool = self.pools.get_mut(&leg.res_addr).unwrap();
+                bucket_map.insert(leg.res_addr, pool.repay_fixed_term(repay_bucket, leg.amount, leg.fee));
+            }
+
+            self.batch_transient_nft_res_mgr.burn(flashloan);
+            bucket_map.into_values().collect()
+        }
+
+        fn is_fee_exempt(&self, fee_exempt: Option<Proof>) -> bool{
+            if let Some(proof) = fee_exempt {
+                let checked_proof = proof.check_with_message(self.fee_exempt_badge, "The proof is not the fee exempt badge!");
+                assert!(checked_proof.amount() >= Decimal::ONE, "The proof is not the fee exempt badge!");
+                true
+            } else {
+                false
+            }
+        }
+
+        fn get_flashloan_fee_ratio(&self, res_addr: ResourceAddress, fee_exempt: Option<Proof>) -> Decimal{
+            if self.is_fee_exempt(fee_exempt) {
+                Decimal::ZERO
+            } else {
+                self.pools.get(&res_addr).unwrap().get_flashloan_fee_ratio()
+            }
+        }
+
+        ///
+        /// Moves the treasury share of the flash loan fee from the pool into the treasury.
+        pub fn withdraw_treasury(&mut self, underlying_token_addr: ResourceAddress, amount: Decimal){
             assert!(self.pools.get(&underlying_token_addr).is_some(), "unknow token resource address.");
             let pool = self.pools.get_mut(&underlying_token_addr).unwrap();
-            pool.withdraw_insurance(amount)
+            let bucket = pool.withdraw_treasury(amount);
+            self.treasury.deposit(bucket, RevenueSource::FlashLoanFee);
+        }
+
+        ///
+        /// Splits the flash loan fee of the pool, the insurance gets `insurance_ratio`, the treasury gets `treasury_ratio`
+        /// and the depositors get the rest.
+        pub fn set_flashloan_fee_split(&mut self, underlying_token_addr: ResourceAddress, insurance_ratio: Decimal, treasury_ratio: Decimal){
+            assert!(self.pools.get(&underlying_token_addr).is_some(), "unknow token resource address.");
+            let pool = self.pools.get_mut(&underlying_token_addr).unwrap();
+            pool.set_flashloan_fee_split(insurance_ratio, treasury_ratio);
+            Runtime::emit_event(SetFlashloanFeeSplitEvent{
+                underlying_token: underlying_token_addr,
+                insurance_ratio,
+                treasury_ratio
+            });
+        }
+
+        ///
+        /// Moves the insurance of the pool into the treasury.
+        pub fn withdraw_insurance(&mut self, underlying_token_addr: ResourceAddress, amount: Decimal){
+            assert!(self.pools.get(&underlying_token_addr).is_some(), "unknow token resource address.");
+            let pool = self.pools.get_mut(&underlying_token_addr).unwrap();
+            let bucket = pool.withdraw_insurance(amount);
+            self.treasury.deposit(bucket, RevenueSource::Insurance);
+        }
+
+        pub fn get_treasury(&self) -> ComponentAddress{
+            self.treasury.address()
         }
 
         pub fn get_interest_rate(&self, underlying_token_addr: ResourceAddress, stable_borrow_amount:Decimal) -> (Decimal, Decimal, Decimal){
@@ -615,39 +1931,74 @@ mod cdp_mgr{
             lending_pool.get_interest_rate(stable_borrow_amount)
         }
 
+        ///
+        /// Reports the status of the CDP. It is valued by the cached oracle prices of current epoch,
+        /// so no price signature is required.
+        pub fn get_cdp_status(&self, id: u64) -> CdpStatus{
+            let cdp_id = NonFungibleLocalId::integer(id);
+            let cdp_data = self.cdp_res_mgr.get_non_fungible_data::<CollateralDebtPosition>(&cdp_id);
+            let prices = self.get_cached_prices_in_xrd(self.get_position_tokens(&cdp_data, None));
+            let (ltv_value, threshold_value, debt_value) = self.get_position_values(&cdp_data, &prices);
+
+            let mut debts: HashMap<ResourceAddress, Decimal> = HashMap::new();
+            for (borrow_token, debt) in cdp_data.debts.iter() {
+                if !debt.is_empty() {
+                    debts.insert(*borrow_token, self.get_debt_amount(*borrow_token, debt));
+                }
+            }
+
+            let mut collateral_value = Decimal::ZERO;
+            let mut liquidation_prices: HashMap<ResourceAddress, Decimal> = HashMap::new();
+            for (dx_token, dx_amount) in cdp_data.collaterals.iter() {
+                let (underlying_token, underlying_amount) = self.get_collateral_redemption(*dx_token, *dx_amount);
+                let (_, liquidation_threshold, _) = self.get_risk_params(&cdp_data, *dx_token);
+                let value = underlying_amount.checked_mul(*prices.get(&underlying_token).unwrap()).unwrap();
+                collateral_value = collateral_value.checked_add(value).unwrap();
+
+                // (debt_value - threshold value of the other collaterals) / (underlying amount * liquidation threshold)
+                let other_threshold_value = threshold_value.checked_sub(value.checked_mul(liquidation_threshold).unwrap()).unwrap();
+                let weighted_amount = underlying_amount.checked_mul(liquidation_threshold).unwrap();
+                let liquidation_price = if debt_value <= other_threshold_value || weighted_amount.is_zero() {
+                    Decimal::ZERO
+                } else {
+                    debt_value.checked_sub(other_threshold_value).unwrap().checked_div(weighted_amount).unwrap()
+                };
+                liquidation_prices.insert(*dx_token, liquidation_price);
+            }
+
+            let available_value = ltv_value.checked_sub(debt_value).unwrap();
+            CdpStatus{
+                collateral_value,
+                debt_value,
+                health_factor: self.get_health_factor(&cdp_data, &prices),
+                borrowable_value: if available_value.is_positive() {available_value} else {Decimal::ZERO},
+                debts,
+                liquidation_prices
+            }
+        }
+
         fn get_liquidate_debt_and_collateral(&self,
-            debt_price: Decimal,
-            collateral_underlying_price: Decimal,
+            cdp_data: &CollateralDebtPosition,
+            prices: &HashMap<ResourceAddress, Decimal>,
             debt_to_cover: Decimal,
             borrow_token: ResourceAddress,
-            underlying_token: ResourceAddress,
-            borrow_amount: Decimal,
-            normalized_borrow: Decimal,
-            collateral_amount: Decimal,
-            is_stable: bool,
-            stable_rate: Decimal,
-            last_update_epoch: u64
+            collateral_token: ResourceAddress
         ) -> (Decimal, Decimal){
-            let underlying_pool = self.pools.get(&underlying_token).unwrap();
-            let debt_pool = self.pools.get(&borrow_token).unwrap();
-            let underlying_state = self.states.get(&underlying_token).unwrap();
-            let liquidation_threshold = underlying_state.liquidation_threshold;
-            let liquidation_bonus = underlying_state.liquidation_bonus;
-
-            let underlying_amount = underlying_pool.get_redemption_value(collateral_amount);
-            let debt_amount = if is_stable {
-                debt_pool.get_stable_interest(borrow_amount, last_update_epoch, stable_rate)
-            }else{
-                debt_pool.get_variable_interest(normalized_borrow)
-            };
-            let underlying_value = underlying_amount.checked_mul(collateral_underlying_price).unwrap();
-            let health_factor = underlying_value.checked_mul(liquidation_threshold).unwrap()
-            .checked_div(debt_amount.checked_mul(debt_price).unwrap()).unwrap();
+            let collateral_amount = cdp_data.get_collateral_amount(&collateral_token);
+            let (underlying_token, underlying_amount) = self.get_collateral_redemption(collateral_token, collateral_amount);
+            let (_, _, liquidation_bonus) = self.get_risk_params(cdp_data, collateral_token);
+            let debt_price = *prices.get(&borrow_token).unwrap();
+            let collateral_underlying_price = *prices.get(&underlying_token).unwrap();
+
+            let health_factor = self.get_health_factor(cdp_data, prices);
             assert!(health_factor <= Decimal::ONE, "Health factor is not below the threshold");
 
+            let debt_amount = self.get_debt_amount(borrow_token, cdp_data.debts.get(&borrow_token).unwrap());
+            let underlying_value = underlying_amount.checked_mul(collateral_underlying_price).unwrap();
+
             let collateral_to_underlying_index = underlying_amount.checked_div(collateral_amount).unwrap();
             let max_to_liquidate = precent_mul(debt_amount, self.close_factor_percent);
-            info!("debt_amount: {}, max_to_liquidate:{}",debt_amount, max_to_liquidate);
+            info!("debt_amount: {}, max_to_liquidate:{}, health_factor:{}",debt_amount, max_to_liquidate, health_factor);
             let mut actual_to_liquidate = if debt_to_cover.is_positive() && max_to_liquidate > debt_to_cover {debt_to_cover} else{max_to_liquidate};
             // debt.amount * debt.price * (1+liquidation_bonus) / underlying.price
             let mut underlying_to_liquidate = actual_to_liquidate.checked_mul(debt_price).unwrap().checked_mul(
@@ -661,6 +2012,8 @@ mod cdp_mgr{
                     debt_price.checked_mul(Decimal::ONE.checked_add(liquidation_bonus).unwrap()).unwrap()
                 ).unwrap();
                 info!("underlying_to_liquidate:{}, underlying_amount:{} actual_to_liquidate:{}", underlying_to_liquidate, underlying_amount, actual_to_liquidate);
+                // the whole collateral is released, avoid the rounding of the index.
+                return (actual_to_liquidate, collateral_amount);
             };
 
             (
@@ -670,206 +2023,574 @@ mod cdp_mgr{
             
         }
 
-        fn update_cdp_after_repay(&mut self, 
-            cdp_id: &NonFungibleLocalId,
-            cdp_data: CollateralDebtPosition,
-            repay_amount: Decimal,
-            delta_borrow: Decimal,
-            delta_normalized_borrow: Decimal,
-            delta_collateral: Decimal
-        ){
-            info!("total_repay:{}-{}", cdp_data.total_repay, repay_amount);
-            self.cdp_res_mgr.update_non_fungible_data(cdp_id, "total_repay", cdp_data.total_repay.checked_add(repay_amount).unwrap());
-            if !cdp_data.is_stable && delta_normalized_borrow != Decimal::ZERO{
-                info!("normalized_borrow:{}-{}", cdp_data.normalized_borrow, delta_normalized_borrow);
-                self.cdp_res_mgr.update_non_fungible_data(cdp_id, "normalized_borrow", cdp_data.normalized_borrow.checked_sub(delta_normalized_borrow).unwrap());
-            }
-
-            if cdp_data.is_stable && delta_borrow != Decimal::ZERO{
-                let new_borrow_amount = cdp_data.borrow_amount - delta_borrow;
-                info!("borrow_amount:{}-{}", cdp_data.borrow_amount, delta_borrow);
-                self.cdp_res_mgr.update_non_fungible_data(cdp_id, "borrow_amount", new_borrow_amount);
-                if new_borrow_amount == Decimal::ZERO {
-                    self.cdp_res_mgr.update_non_fungible_data(cdp_id, "stable_rate", Decimal::ZERO);
+        ///
+        /// All collaterals of the CDP are exhausted, the remaining debts can no longer be repaid.
+        /// They are written off from the pools, the loss is covered by the insurance first, then by the depositors.
+        fn write_off_bad_debts(&mut self, id: u64, cdp_data: &mut CollateralDebtPosition, isolated_token: Option<ResourceAddress>){
+            let borrow_tokens: Vec<ResourceAddress> = cdp_data.debts.iter().filter(|(_, debt)| !debt.is_empty()).map(|(token, _)| *token).collect();
+            for borrow_token in borrow_tokens {
+                let mut debt = cdp_data.debts.get(&borrow_token).cloned().unwrap();
+                let borrow_pool = self.pools.get_mut(&borrow_token).unwrap();
+                let (bad_debt, insurance_loss, socialized_loss) = if debt.is_stable {
+                    borrow_pool.write_off_stable(debt.borrow_amount, debt.stable_rate, debt.last_update_epoch)
+                } else {
+                    borrow_pool.write_off_variable(debt.normalized_borrow)
+                };
+                let (deposit_index, _) = borrow_pool.get_current_index();
+                info!("write off {}: bad_debt:{}, insurance:{}, socialized:{}", Runtime::bech32_encode_address(borrow_token), bad_debt, insurance_loss, socialized_loss);
+
+                // the principal not yet repaid leaves the debt ceiling of the isolated collateral.
+                let outstanding_principal = debt.total_borrow.checked_sub(debt.total_repay).unwrap();
+                debt.normalized_borrow = Decimal::ZERO;
+                debt.borrow_amount = Decimal::ZERO;
+                debt.stable_rate = Decimal::ZERO;
+                debt.last_update_epoch = borrow_pool.get_accrual_point();
+                cdp_data.debts.insert(borrow_token, debt);
+                if let Some(token) = isolated_token {
+                    if outstanding_principal.is_positive() {
+                        self.reduce_isolated_debt_of(token, outstanding_principal);
+                    }
                 }
-                self.cdp_res_mgr.update_non_fungible_data(cdp_id, "last_update_epoch", Runtime::current_epoch().number());
+
+                Runtime::emit_event(BadDebtEvent{
+                    cdp_id: id,
+                    borrow_token,
+                    bad_debt,
+                    insurance_loss,
+                    socialized_loss,
+                    deposit_index
+                });
             }
+        }
+
+        fn get_isolated_collateral(&self, cdp_data: &CollateralDebtPosition) -> Option<ResourceAddress>{
+            cdp_data.collaterals.keys()
+                .filter(|collateral_token| !self.staking_collaterals.contains_key(collateral_token))
+                .map(|dx_token| get_underlying_token_res_addr(*dx_token))
+                .find(|underlying_token| self.states.get(underlying_token).is_some_and(|state| state.is_isolated))
+        }
 
-            if delta_collateral != Decimal::ZERO{
-                info!("collateral_amount:{}|{}", cdp_data.collateral_amount, delta_collateral);
-                self.cdp_res_mgr.update_non_fungible_data(&cdp_id, "collateral_amount", cdp_data.collateral_amount + delta_collateral);
+        /// An isolated collateral can not be used together with other collaterals in the same CDP.
+        fn assert_isolation_collateral(&self, cdp_data: &CollateralDebtPosition, dx_token: ResourceAddress){
+            if cdp_data.collaterals.keys().any(|collateral_token| *collateral_token != dx_token) {
+                let is_isolated = !self.staking_collaterals.contains_key(&dx_token)
+                    && self.states.get(&get_underlying_token_res_addr(dx_token)).unwrap().is_isolated;
+                assert!(
+                    !is_isolated && self.get_isolated_collateral(cdp_data).is_none(),
+                    "An isolated collateral can not be used together with other collaterals."
+                );
             }
         }
 
-        fn update_cdp_data(&mut self,
-            is_stable: bool,
-            delta_borrow: Decimal,
-            interest: Decimal,
-            delta_collateral: Decimal,
-            delta_normalized_borrow: Decimal,
-            cdp_avg_rate:Decimal,
-            cdp_id: NonFungibleLocalId,
-            data: CollateralDebtPosition
-        ){
-            if delta_borrow != Decimal::ZERO || interest != Decimal::ZERO {
-                self.cdp_res_mgr.update_non_fungible_data(&cdp_id, "total_borrow", data.total_borrow + delta_borrow);
-                self.cdp_res_mgr.update_non_fungible_data(&cdp_id, "borrow_amount", data.borrow_amount + delta_borrow + interest);
+        /// Validates the borrow of an isolated CDP, and accumulates the debt backed by the isolated collateral.
+        fn add_isolated_debt(&mut self, cdp_data: &CollateralDebtPosition, borrow_token: ResourceAddress, amount: Decimal){
+            if let Some(isolated_token) = self.get_isolated_collateral(cdp_data) {
+                assert!(
+                    self.states.get(&borrow_token).is_some_and(|state| state.borrowable_in_isolation),
+                    "The asset is not borrowable against an isolated collateral."
+                );
+                let state = self.states.get_mut(&isolated_token).unwrap();
+                let isolated_debt = state.isolated_debt.checked_add(amount).unwrap();
+                assert!(isolated_debt <= state.debt_ceiling, "The debt exceeds the debt ceiling of the isolated collateral.");
+                state.isolated_debt = isolated_debt;
             }
-            if delta_normalized_borrow != Decimal::ZERO {
-                self.cdp_res_mgr.update_non_fungible_data(&cdp_id, "normalized_borrow", data.normalized_borrow + delta_normalized_borrow);
+        }
+
+        /// Releases the repaid principal from the debt backed by the isolated collateral.
+        fn reduce_isolated_debt(&mut self, cdp_data: &CollateralDebtPosition, principal_amount: Decimal){
+            if let Some(isolated_token) = self.get_isolated_collateral(cdp_data) {
+                self.reduce_isolated_debt_of(isolated_token, principal_amount);
             }
-            if delta_collateral != Decimal::ZERO {
-                self.cdp_res_mgr.update_non_fungible_data(&cdp_id, "collateral_amount", data.collateral_amount + delta_collateral);
+        }
+
+        fn reduce_isolated_debt_of(&mut self, isolated_token: ResourceAddress, principal_amount: Decimal){
+            let state = self.states.get_mut(&isolated_token).unwrap();
+            state.isolated_debt = if principal_amount >= state.isolated_debt { Decimal::ZERO } else { state.isolated_debt.checked_sub(principal_amount).unwrap() };
+        }
+
+        fn assert_supply_cap(&self, underlying_token: ResourceAddress, amount: Decimal){
+            if let Some(supply_cap) = self.states.get(&underlying_token).and_then(|state| state.supply_cap) {
+                let (total_supply, _) = self.pools.get(&underlying_token).unwrap().get_total_supply_borrow();
+                assert!(total_supply.checked_add(amount).unwrap() <= supply_cap, "The supply exceeds the supply cap of the asset.");
             }
-            if is_stable {
-                self.cdp_res_mgr.update_non_fungible_data(&cdp_id, "stable_rate", cdp_avg_rate);
-                self.cdp_res_mgr.update_non_fungible_data(&cdp_id, "last_update_epoch", Runtime::current_epoch().number());
+        }
+
+        fn assert_borrow_cap(&self, borrow_token: ResourceAddress, amount: Decimal){
+            if let Some(borrow_cap) = self.states.get(&borrow_token).and_then(|state| state.borrow_cap) {
+                let (_, total_borrow) = self.pools.get(&borrow_token).unwrap().get_total_supply_borrow();
+                assert!(total_borrow.checked_add(amount).unwrap() <= borrow_cap, "The borrow exceeds the borrow cap of the asset.");
             }
-            
         }
 
-        fn new_cdp(&mut self,
-            dx_addr: ResourceAddress,
-            borrow_token: ResourceAddress,
-            borrow_amount: Decimal,
-            collateral_amount: Decimal,
-            borrow_normalized_amount: Decimal,
-            cdp_avg_rate: Decimal,
-            is_stable: bool
-        ) -> NonFungibleBucket{
-            let epoch_at = if is_stable {Runtime::current_epoch().number()} else{0u64};
-            let data = CollateralDebtPosition{
-                collateral_token: dx_addr.clone(),
-                total_borrow: borrow_amount,
-                total_repay: Decimal::ZERO,
-                normalized_borrow: borrow_normalized_amount,
-                last_update_epoch: epoch_at,
-                stable_rate: cdp_avg_rate,
-                collateral_amount,
-                borrow_amount,
-                is_stable,
-                borrow_token
+        fn save_cdp(&self, cdp_id: &NonFungibleLocalId, cdp_data: &CollateralDebtPosition){
+            self.cdp_res_mgr.update_non_fungible_data(cdp_id, "collaterals", cdp_data.collaterals.clone());
+            self.cdp_res_mgr.update_non_fungible_data(cdp_id, "debts", cdp_data.debts.clone());
+        }
+
+        fn new_cdp_data(&self, dx_token: ResourceAddress, dx_amount: Decimal) -> CollateralDebtPosition{
+            self.assert_collateral_token(dx_token);
+            let mut cdp_data = CollateralDebtPosition{
+                collaterals: HashMap::new(),
+                debts: HashMap::new(),
+                emode_category: None
             };
+            cdp_data.add_collateral(dx_token, dx_amount);
+            cdp_data
+        }
+
+        fn get_accrual_point(&self, borrow_token: ResourceAddress) -> u64{
+            self.pools.get(&borrow_token).unwrap().get_accrual_point()
+        }
+
+        fn get_loan_index(&self, borrow_token: ResourceAddress) -> Decimal{
+            let (_, loan_index) = self.pools.get(&borrow_token).unwrap().get_current_index();
+            loan_index
+        }
+
+        fn new_cdp(&mut self, data: CollateralDebtPosition) -> NonFungibleBucket{
             self.cdp_id_counter += 1;
             self.cdp_res_mgr.mint_non_fungible(&NonFungibleLocalId::integer(self.cdp_id_counter), data)
         }
 
-        // fn get_token(&self, dx_token: ResourceAddress) -> (ResourceAddress, Decimal, Decimal){
-        //     let underlying_token = *self.deposit_asset_map.get(&dx_token).unwrap();
-        //     let underlying_pool = self.get_
-        //     (underlying_token, self)
-        // }
-
         ///
-        /// Calculate the maximum loan amount based on the provided parameters.                                         
-        /// |   borrow   |   collateral      |   price(base/quote) | stage                                          |
-        /// | ---------- | ----------------- | ------------------- | ---------------------------------------------- |
-        /// | XRD        | USDC              | XRD/USDC            | borrow=price1.base, collateral=price1.quote    |
-        /// | USDT       | USDC              | XRD/USDC, XRD/USDT  | borrow=price1.quote, collateral=price2.quote   |
-        /// | USDT       | XRD               | XRD/USDT            | borrow=price1.quote, collateral=price1.base    |
-        /// | USDC       | XRD               | XRD/USDC            | borrow=price1.quote, collateral=price1.base    |
+        /// Returns the current debt (principal and interest) of a debt position.
+        fn get_debt_amount(&self, borrow_token: ResourceAddress, debt: &DebtPosition) -> Decimal{
+            let borrow_pool = self.pools.get(&borrow_token).unwrap();
+            if debt.is_stable {
+                debt.borrow_amount.checked_add(
+                    borrow_pool.get_stable_interest(debt.borrow_amount, debt.last_update_epoch, debt.stable_rate)
+                ).unwrap()
+            } else {
+                borrow_pool.get_variable_interest(debt.normalized_borrow)
+            }
+        }
+
         ///
-        fn get_max_loan_amount(&self,
-            dx_token: ResourceAddress,
-            dx_amount: Decimal,
-            borrow_token: ResourceAddress,
-            borrow_price_in_xrd: Decimal,
-            collateral_price_in_xrd: Decimal,
-            remove_amount: Decimal
-        ) -> Decimal {
-            let collateral_token = get_underlying_token_res_addr(dx_token);
-            let underlying_pool = self.pools.get(&collateral_token).unwrap();
-            let underlying_state = self.states.get(&collateral_token).unwrap();
-            let underlying_amount = underlying_pool.get_redemption_value(dx_amount);
-            let amount = underlying_amount.checked_sub(remove_amount).unwrap();
-            let ltv = underlying_state.ltv;
-            assert!(ltv > Decimal::ZERO, "Loan to Value(LTV) of the collateral asset equals ZERO!");
-            
-            info!(
-                "get_max_loan_amount: {}|{},{}*{}, price:{}/{}, underlying_amount:{}, remove_amount:{}",
-                Runtime::bech32_encode_address(dx_token),dx_amount, amount,ltv,borrow_price_in_xrd,collateral_price_in_xrd, underlying_amount, remove_amount
-            );
+        /// Returns the (ltv, liquidation_threshold, liquidation_bonus) of the collateral for the CDP.
+        /// The parameters of the efficiency mode category apply when the CDP and the collateral belong to the same category.
+        fn get_risk_params(&self, cdp_data: &CollateralDebtPosition, collateral_token: ResourceAddress) -> (Decimal, Decimal, Decimal){
+            let (params, emode_category) = if let Some(state) = self.staking_collaterals.get(&collateral_token) {
+                ((state.ltv, state.liquidation_threshold, state.liquidation_bonus), state.emode_category)
+            } else {
+                let state = self.states.get(&get_underlying_token_res_addr(collateral_token)).unwrap();
+                ((state.ltv, state.liquidation_threshold, state.liquidation_bonus), state.emode_category)
+            };
+            if let Some(category_id) = cdp_data.emode_category {
+                if emode_category == Some(category_id) {
+                    let category = self.emode_categories.get(&category_id).unwrap();
+                    return (category.ltv, category.liquidation_threshold, category.liquidation_bonus);
+                }
+            }
+            params
+        }
 
+        /// The underlying token of the collateral, XRD for LSU and dseXRD.
+        fn get_collateral_underlying(&self, collateral_token: ResourceAddress) -> ResourceAddress{
+            if self.staking_collaterals.contains_key(&collateral_token) {
+                XRD
+            } else {
+                get_underlying_token_res_addr(collateral_token)
+            }
+        }
 
-            let divisibility = get_divisibility(borrow_token);
-            if ltv.is_zero() || divisibility.is_none() {
-                return Decimal::ZERO;
+        ///
+        /// Returns (underlying token, amount of underlying token) that the collateral can be redeemed for.
+        /// LSU is valued by the validator, dseXRD by the staking pool and dx token by the lending pool.
+        fn get_collateral_redemption(&self, collateral_token: ResourceAddress, amount: Decimal) -> (ResourceAddress, Decimal){
+            match self.staking_collaterals.get(&collateral_token).map(|state| state.kind.clone()) {
+                Some(StakingCollateralKind::Lsu(validator_addr)) => {
+                    let validator: Global<Validator> = Global::from(validator_addr);
+                    (XRD, validator.get_redemption_value(amount))
+                },
+                Some(StakingCollateralKind::Dse(pool_addr)) => {
+                    let staking_pool = Global::<StakingResourePool>::from(pool_addr);
+                    (XRD, staking_pool.get_redemption_value(amount))
+                },
+                None => {
+                    let underlying_token = get_underlying_token_res_addr(collateral_token);
+                    (underlying_token, self.pools.get(&underlying_token).unwrap().get_redemption_value(amount))
+                }
             }
+        }
 
-            if borrow_token == XRD && collateral_price_in_xrd.is_positive(){
-                return floor(collateral_price_in_xrd.checked_mul(amount).unwrap()
-                .checked_mul(ltv).unwrap()
-                .checked_div(borrow_price_in_xrd).unwrap(), divisibility.unwrap());
+        ///
+        /// Takes the collateral out of the vault. dx tokens are redeemed for the underlying tokens unless `as_dx_token`,
+        /// LSU and dseXRD are handed out as they are.
+        fn release_collateral(&mut self, collateral_token: ResourceAddress, amount: Decimal, as_dx_token: bool) -> FungibleBucket{
+            let collateral_bucket = self.collateral_vaults.take_advanced(&collateral_token, amount, TO_ZERO);
+            if as_dx_token || self.staking_collaterals.contains_key(&collateral_token) {
+                return collateral_bucket;
             }
-            
-            if borrow_token != XRD && collateral_token != XRD {
-                if borrow_price_in_xrd.is_positive() && collateral_price_in_xrd.is_positive() {
-                    return floor(collateral_price_in_xrd.checked_mul(amount).unwrap()
-                    .checked_mul(ltv).unwrap()
-                    .checked_div(borrow_price_in_xrd).unwrap(), divisibility.unwrap());
+            let underlying_token = get_underlying_token_res_addr(collateral_token);
+            let underlying_pool = self.pools.get_mut(&underlying_token).unwrap();
+            underlying_pool.redeem_collateral(collateral_bucket)
+        }
+
+        ///
+        /// Calculate the values (in XRD) of the whole position.
+        /// Returns (collateral value weighted by LTV, collateral value weighted by liquidation threshold, debt value)
+        fn get_position_values(&self, cdp_data: &CollateralDebtPosition, prices: &HashMap<ResourceAddress, Decimal>) -> (Decimal, Decimal, Decimal){
+            let mut ltv_value = Decimal::ZERO;
+            let mut threshold_value = Decimal::ZERO;
+            for (dx_token, dx_amount) in cdp_data.collaterals.iter() {
+                let (underlying_token, underlying_amount) = self.get_collateral_redemption(*dx_token, *dx_amount);
+                let (ltv, liquidation_threshold, _) = self.get_risk_params(cdp_data, *dx_token);
+                let value = underlying_amount.checked_mul(*prices.get(&underlying_token).unwrap()).unwrap();
+                ltv_value = ltv_value.checked_add(value.checked_mul(ltv).unwrap()).unwrap();
+                threshold_value = threshold_value.checked_add(value.checked_mul(liquidation_threshold).unwrap()).unwrap();
+            }
+
+            let mut debt_value = Decimal::ZERO;
+            for (borrow_token, debt) in cdp_data.debts.iter() {
+                if debt.is_empty() {
+                    continue;
                 }
+                let debt_amount = self.get_debt_amount(*borrow_token, debt);
+                debt_value = debt_value.checked_add(debt_amount.checked_mul(*prices.get(borrow_token).unwrap()).unwrap()).unwrap();
             }
-            
-            if collateral_token == XRD && borrow_price_in_xrd.is_positive() {
-                return floor(
-                    collateral_price_in_xrd.checked_mul(amount).unwrap()
-                    .checked_mul(ltv).unwrap()
-                    .checked_div(borrow_price_in_xrd).unwrap(),
-                    divisibility.unwrap()
-                );
+            info!("get_position_values: ltv_value:{}, threshold_value:{}, debt_value:{}", ltv_value, threshold_value, debt_value);
+            (ltv_value, threshold_value, debt_value)
+        }
+
+        /// health factor = sum(collateral value * liquidation threshold) / sum(debt value)
+        fn get_health_factor(&self, cdp_data: &CollateralDebtPosition, prices: &HashMap<ResourceAddress, Decimal>) -> Decimal{
+            let (_, threshold_value, debt_value) = self.get_position_values(cdp_data, prices);
+            if debt_value.is_zero() {
+                return Decimal::MAX;
             }
+            threshold_value.checked_div(debt_value).unwrap()
+        }
 
-            Decimal::ZERO
-            
+        /// The value (in XRD) that can still be borrowed against the position, negative if the position exceeds its LTV.
+        fn get_available_value(&self, cdp_data: &CollateralDebtPosition, prices: &HashMap<ResourceAddress, Decimal>) -> Decimal{
+            let (ltv_value, _, debt_value) = self.get_position_values(cdp_data, prices);
+            ltv_value.checked_sub(debt_value).unwrap()
         }
 
-        fn extra_params(&self,
-            dx_token: ResourceAddress,
+        fn assert_borrowable(&self,
+            cdp_data: &CollateralDebtPosition,
             borrow_token: ResourceAddress,
-            price1: &String,
-            quote1: ResourceAddress,
-            timestamp1: u64,
-            signature1: &String,
-            price2: Option<String>,
-            quote2: Option<ResourceAddress>,
-            timestamp2: Option<u64>,
-            signature2: Option<String>
-        ) -> (Decimal, Decimal){
-            let collateral_underlying_token = get_underlying_token_res_addr(dx_token);
-            self.get_price_in_xrd(collateral_underlying_token, borrow_token, &price1, quote1, timestamp1, &signature1, price2, quote2, timestamp2, signature2)
+            borrow_amount: Decimal,
+            prices: &HashMap<ResourceAddress, Decimal>
+        ){
+            let borrow_value = borrow_amount.checked_mul(*prices.get(&borrow_token).unwrap()).unwrap();
+            let available_value = self.get_available_value(cdp_data, prices);
+            info!("borrow {}|{}, value:{}, available_value:{}", Runtime::bech32_encode_address(borrow_token), borrow_amount, borrow_value, available_value);
+            assert!(borrow_value <= available_value, "The amount borrowed exceeds the borrowable quantity of the collateral.");
         }
 
-        fn get_price_in_xrd(&self,
-            collateral_token: ResourceAddress,
-            borrow_token: ResourceAddress,
-            price1: &String,
-            quote1: ResourceAddress,
-            timestamp1: u64,
-            signature1: &String,
-            price2: Option<String>,
-            quote2: Option<ResourceAddress>,
-            timestamp2: Option<u64>,
-            signature2: Option<String>
-        ) -> (Decimal, Decimal){
-            let mut price_oracle = Global::<PriceOracle>::from(ORACLE_COMPONENT);
-            if borrow_token == XRD && collateral_token == quote1 {
-                let collateral_price_in_xrd = price_oracle.get_valid_price_in_xrd(quote1, price1.clone(), timestamp1, signature1.clone());
-                return (Decimal::ONE, collateral_price_in_xrd);
+        ///
+        /// All tokens that need a price to value the position: the underlying tokens of the collaterals,
+        /// the tokens of the outstanding debts and optionally the token about to be borrowed.
+        fn get_position_tokens(&self, cdp_data: &CollateralDebtPosition, borrow_token: Option<ResourceAddress>) -> Vec<ResourceAddress>{
+            let mut tokens: Vec<ResourceAddress> = cdp_data.collaterals.keys().map(|dx_token| self.get_collateral_underlying(*dx_token)).collect();
+            tokens.extend(cdp_data.debts.iter().filter(|(_, debt)| !debt.is_empty()).map(|(token, _)| *token));
+            if let Some(token) = borrow_token {
+                tokens.push(token);
             }
-            
-            if borrow_token == quote1 && quote2.is_some() && collateral_token == quote2.unwrap(){
-                let collateral_price_in_xrd = price_oracle.get_valid_price_in_xrd(quote2.unwrap(), price2.unwrap(), timestamp2.unwrap(), signature2.unwrap());
-                let borrow_price_in_xrd = price_oracle.get_valid_price_in_xrd(quote1, price1.clone(), timestamp1, signature1.clone());
-                return (borrow_price_in_xrd, collateral_price_in_xrd);
+            tokens
+        }
+
+        ///
+        /// Resolve the price in XRD of each token from the signed quotes of the price bundle.
+        /// Every quote is signed as XRD/quote, so the price of any pair is the cross rate through XRD:
+        /// price(collateral/borrow) = price(collateral/XRD) / price(borrow/XRD)
+        fn get_prices_in_xrd(&self, tokens: Vec<ResourceAddress>, price_bundle: &PriceBundle) -> HashMap<ResourceAddress, Decimal>{
+            let mut price_oracle = Global::<PriceOracle>::from(ORACLE_COMPONENT);
+            let mut prices: HashMap<ResourceAddress, Decimal> = HashMap::new();
+            for token in tokens {
+                if prices.contains_key(&token) {
+                    continue;
+                }
+                let price_in_xrd = self.get_price_in_xrd(&mut price_oracle, token, price_bundle);
+                prices.insert(token, price_in_xrd);
             }
-            
-            if borrow_token == quote1 && collateral_token == XRD {
-                let borrow_price_in_xrd = price_oracle.get_valid_price_in_xrd(quote1, price1.clone(), timestamp1, signature1.clone());
-                return (borrow_price_in_xrd, Decimal::ONE);
+            prices
+        }
+
+        fn get_price_in_xrd(&self, price_oracle: &mut Global<PriceOracle>, token: ResourceAddress, price_bundle: &PriceBundle) -> Decimal{
+            if token == XRD {
+                return Decimal::ONE;
             }
+            let signed_quote = price_bundle.get_quote(token).unwrap_or_else(
+                || panic!("Missing signed price quote of the token {} in the price bundle.", Runtime::bech32_encode_address(token))
+            );
+            let price_in_xrd = price_oracle.get_valid_price_in_xrd(
+                signed_quote.quote, signed_quote.price.clone(), signed_quote.timestamp, signed_quote.signature.clone()
+            );
+            assert!(price_in_xrd.is_positive(), "Incorrect information on price signature of the token {}.", Runtime::bech32_encode_address(token));
+            price_in_xrd
+        }
 
-            (Decimal::ZERO, Decimal::ZERO)
+        fn get_cached_prices_in_xrd(&self, tokens: Vec<ResourceAddress>) -> HashMap<ResourceAddress, Decimal>{
+            let price_oracle = Global::<PriceOracle>::from(ORACLE_COMPONENT);
+            let mut prices: HashMap<ResourceAddress, Decimal> = HashMap::new();
+            for token in tokens {
+                if prices.contains_key(&token) {
+                    continue;
+                }
+                let price_in_xrd = if token == XRD { Decimal::ONE } else { price_oracle.get_price_quote_in_xrd(token) };
+                assert!(price_in_xrd.is_positive(), "There is no cached price of the token {} in current epoch.", Runtime::bech32_encode_address(token));
+                prices.insert(token, price_in_xrd);
+            }
+            prices
         }
 
     }
-}
\ No newline at end of file
+}
+
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SetCapsEvent{
+    pub underlying_token: ResourceAddress,
+    pub supply_cap: Option<Decimal>,
+    pub borrow_cap: Option<Decimal>
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SetIsolationModeEvent{
+    pub underlying_token: ResourceAddress,
+    pub is_isolated: bool,
+    pub debt_ceiling: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SetBorrowableInIsolationEvent{
+    pub underlying_token: ResourceAddress,
+    pub borrowable: bool
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SetEModeCategoryEvent{
+    pub category_id: u8,
+    pub label: String,
+    pub ltv: Decimal,
+    pub liquidation_threshold: Decimal,
+    pub liquidation_bonus: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SetAssetEModeEvent{
+    pub underlying_token: ResourceAddress,
+    pub category_id: Option<u8>
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SetStakingCollateralEvent{
+    pub collateral_token: ResourceAddress,
+    pub ltv: Decimal,
+    pub liquidation_threshold: Decimal,
+    pub liquidation_bonus: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct BadDebtEvent{
+    pub cdp_id: u64,
+    pub borrow_token: ResourceAddress,
+    // the debt(principal and interest) written off from the pool
+    pub bad_debt: Decimal,
+    // the part of the bad debt covered by the insurance balance
+    pub insurance_loss: Decimal,
+    // the part of the bad debt socialized to the floating and the fixed-term depositors by reducing their indexes
+    pub socialized_loss: Decimal,
+    // the deposit index after the write-off
+    pub deposit_index: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SetFlashloanFeeSplitEvent{
+    pub underlying_token: ResourceAddress,
+    pub insurance_ratio: Decimal,
+    pub treasury_ratio: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct NewPoolEvent{
+    pub underlying_token: ResourceAddress,
+    pub dx_token: ResourceAddress,
+    pub ltv: Decimal,
+    pub liquidation_threshold: Decimal,
+    pub liquidation_bonus: Decimal,
+    pub insurance_ratio: Decimal,
+    pub flashloan_fee_ratio: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct BorrowEvent{
+    pub cdp_id: u64,
+    pub collateral_token: ResourceAddress,
+    pub collateral_amount: Decimal,
+    pub borrow_token: ResourceAddress,
+    pub amount: Decimal,
+    // the variable loan shares of the borrow, zero for the stable borrow
+    pub normalized_amount: Decimal,
+    // the variable loan index after the borrow
+    pub loan_index: Decimal,
+    pub is_stable: bool,
+    // zero for the variable borrow
+    pub stable_rate: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ExtendBorrowEvent{
+    pub cdp_id: u64,
+    pub borrow_token: ResourceAddress,
+    pub amount: Decimal,
+    // the variable loan shares of the borrow, zero for the stable borrow
+    pub normalized_amount: Decimal,
+    // the variable loan index after the borrow
+    pub loan_index: Decimal,
+    pub is_stable: bool,
+    // the re-weighted stable rate of the debt, zero for the variable borrow
+    pub stable_rate: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct RepayEvent{
+    pub cdp_id: u64,
+    pub borrow_token: ResourceAddress,
+    pub amount: Decimal,
+    // the variable loan shares repaid, zero for the stable debt
+    pub normalized_amount: Decimal,
+    // the variable loan index after the repayment
+    pub loan_index: Decimal,
+    pub is_stable: bool
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SwapRateModeEvent{
+    pub cdp_id: u64,
+    pub borrow_token: ResourceAddress,
+    // the interest rate mode after the swap
+    pub is_stable: bool,
+    // the stable loan after the swap, zero when swapped to variable
+    pub borrow_amount: Decimal,
+    // the variable loan shares after the swap, zero when swapped to stable
+    pub normalized_amount: Decimal,
+    pub stable_rate: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct RebalanceStableRateEvent{
+    pub cdp_id: u64,
+    pub borrow_token: ResourceAddress,
+    // the stable loan with the capitalized interest
+    pub borrow_amount: Decimal,
+    pub previous_rate: Decimal,
+    pub stable_rate: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct MergeCdpsEvent{
+    pub cdp_id: u64,
+    // the CDPs merged into the CDP and burnt
+    pub merged_cdp_ids: Vec<u64>
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SplitCdpEvent{
+    pub cdp_id: u64,
+    pub new_cdp_id: u64,
+    pub fraction: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SetCdpEModeEvent{
+    pub cdp_id: u64,
+    pub category_id: Option<u8>
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct DelegateCreditEvent{
+    pub cdp_id: u64,
+    pub delegation_id: u64,
+    pub borrow_token: ResourceAddress,
+    pub allowance: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct RevokeCreditEvent{
+    pub cdp_id: u64,
+    pub delegation_id: u64,
+    // the allowance left on the delegation when it was revoked
+    pub revoked_allowance: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct AddCollateralEvent{
+    pub cdp_id: u64,
+    pub collateral_token: ResourceAddress,
+    pub amount: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct WithdrawCollateralEvent{
+    pub cdp_id: u64,
+    pub collateral_token: ResourceAddress,
+    // the amount removed from the CDP, in units of the collateral token
+    pub amount: Decimal,
+    // the token handed out: the underlying token, the dx token or the staking collateral itself
+    pub withdraw_token: ResourceAddress,
+    pub withdraw_amount: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct CloseCdpEvent{
+    pub cdp_id: u64
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct LiquidationEvent{
+    pub cdp_id: u64,
+    pub borrow_token: ResourceAddress,
+    pub debt_amount: Decimal,
+    pub collateral_token: ResourceAddress,
+    // the amount seized from the CDP, in units of the collateral token
+    pub collateral_amount: Decimal,
+    pub withdraw_token: ResourceAddress,
+    pub withdraw_amount: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct FlashLoanBorrowEvent{
+    pub flashloan_id: u64,
+    pub res_addr: ResourceAddress,
+    pub amount: Decimal,
+    pub fee: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct FlashLoanRepayEvent{
+    pub flashloan_id: u64,
+    pub res_addr: ResourceAddress,
+    pub amount: Decimal,
+    pub fee: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct QueueRiskParamChangeEvent{
+    pub change_id: u64,
+    pub underlying_token: ResourceAddress,
+    pub param: RiskParam,
+    pub effective_epoch: u64
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ExecuteRiskParamChangeEvent{
+    pub change_id: u64,
+    pub underlying_token: ResourceAddress,
+    pub param: RiskParam
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct CancelRiskParamChangeEvent{
+    pub change_id: u64,
+    pub underlying_token: ResourceAddress,
+    pub param: RiskParam
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SetAccrualModeEvent{
+    pub underlying_token: ResourceAddress,
+    pub accrual_mode: AccrualMode
+}
@@ -3,10 +3,12 @@ use common::*;
 use keeper::UnstakeData;
 use crate::pool::staking::staking_pool::StakingResourePool;
 use crate::cdp::cdp_mgr::CollateralDebtManager;
+use crate::treasury::RevenueSource;
+use crate::treasury::treasury::Treasury;
 
 
 #[blueprint]
-#[events(NormalRedeemEvent, FasterRedeemEvent, NftFasterRedeemEvent, ClaimXrdEvent)]
+#[events(NormalRedeemEvent, FasterRedeemEvent, NftFasterRedeemEvent, ClaimXrdEvent, SetPausedEvent, SetPerformanceFeeEvent)]
 mod staking_earning {
     const AUTHORITY_RESOURCE: ResourceAddress = _AUTHORITY_RESOURCE;
     const BASE_AUTHORITY_RESOURCE: ResourceAddress = _BASE_AUTHORITY_RESOURCE;
@@ -20,17 +22,28 @@ mod staking_earning {
             authority => updatable_by: [];
             admin => updatable_by: [authority];
             operator => updatable_by: [authority];
+            guardian => updatable_by: [authority];
         },
         methods {            
+            unpause => restrict_to: [admin];
+            pause => restrict_to: [guardian, admin];
+            set_performance_fee => restrict_to: [admin];
+
             join => PUBLIC;
             claim_xrd => PUBLIC;
             redeem => PUBLIC;
+            is_paused => PUBLIC;
         }
     }
 
     struct StakingEarning{
         staking_pool: Global<StakingResourePool>,
-        dse_token: ResourceAddress
+        dse_token: ResourceAddress,
+        // the operations paused by the guardian, I.E.: dseXRD ==> {Redeem}
+        paused_operations: HashMap<ResourceAddress, HashSet<Operation>>,
+        // the share of the XRD advanced by the faster redemption that goes into the treasury
+        performance_fee_ratio: Decimal,
+        treasury: Option<Global<Treasury>>
     }
 
     impl StakingEarning{
@@ -48,7 +61,10 @@ mod staking_earning {
             
             let component = Self{
                 staking_pool,
-                dse_token
+                dse_token,
+                paused_operations: HashMap::new(),
+                performance_fee_ratio: Decimal::ZERO,
+                treasury: None
             }.instantiate()
             .prepare_to_globalize(owner_role)
             .with_address(address_reservation)
@@ -56,11 +72,69 @@ mod staking_earning {
                 authority => rule!(require(AUTHORITY_RESOURCE));
                 admin => admin_rule.clone();
                 operator => op_rule.clone();
+                guardian => op_rule.clone();
             })
             .globalize();
             component
         }
 
+        ///
+        /// Pauses `join` (XRD) or `redeem` (dseXRD or LSU) of the asset, only the admin can unpause it.
+        pub fn pause(&mut self, asset: ResourceAddress, operation: Operation){
+            self.set_paused(asset, operation, true);
+        }
+
+        pub fn unpause(&mut self, asset: ResourceAddress, operation: Operation){
+            self.set_paused(asset, operation, false);
+        }
+
+        pub fn is_paused(&self, asset: ResourceAddress, operation: Operation) -> bool{
+            self.paused_operations.get(&asset).is_some_and(|operations| operations.contains(&operation))
+        }
+
+        fn set_paused(&mut self, asset: ResourceAddress, operation: Operation, paused: bool){
+            assert!(operation == Operation::Join || operation == Operation::Redeem, "The operation {:?} is paused by the CDP manager!", operation);
+            let operations = self.paused_operations.entry(asset).or_default();
+            if paused {
+                operations.insert(operation);
+            } else {
+                operations.remove(&operation);
+            }
+            Runtime::emit_event(SetPausedEvent{
+                asset,
+                operation,
+                paused
+            });
+        }
+
+        fn assert_not_paused(&self, asset: ResourceAddress, operation: Operation){
+            assert!(!self.is_paused(asset, operation), "The operation {:?} of the asset is paused!", operation);
+        }
+
+        ///
+        /// Charges `fee_ratio` of the XRD advanced by the faster redemption as the performance fee,
+        /// the fee is deposited into the treasury of the CDP manager.
+        pub fn set_performance_fee(&mut self, treasury: ComponentAddress, fee_ratio: Decimal){
+            assert!(!fee_ratio.is_negative() && fee_ratio < Decimal::ONE, "The fee ratio must be between zero and one!");
+            self.treasury = Some(Global::<Treasury>::from(treasury));
+            self.performance_fee_ratio = fee_ratio;
+            Runtime::emit_event(SetPerformanceFeeEvent{
+                treasury,
+                fee_ratio
+            });
+        }
+
+        fn charge_performance_fee(&mut self, xrd_bucket: &mut FungibleBucket) -> Decimal{
+            let fee = floor_by_resource(XRD, xrd_bucket.amount().checked_mul(self.performance_fee_ratio).unwrap());
+            match self.treasury.as_mut() {
+                Some(treasury) if fee.is_positive() => {
+                    treasury.deposit(xrd_bucket.take(fee), RevenueSource::PerformanceFee);
+                    fee
+                },
+                _ => Decimal::ZERO
+            }
+        }
+
         ///
         /// Claims XRD using claim NFTs.
         /// claims matured XRD, and accumulates unmatured claims for instant redemption.
@@ -135,13 +209,16 @@ mod staking_earning {
                 let borrow_amount = unmatured_claim_amount.checked_sub(unmatured_interest_amount).unwrap();
                 #[cfg(feature = "verbose")]
                 info!("Gase used in claim_xrd cdp_mgr.staking_borrow before: {}", init- Runtime::get_fee_balance());
-                xrd_bucket.put(cdp_mgr.staking_borrow(XRD, borrow_amount, unmatured_claim_nfts, interests));
+                let mut borrow_bucket = cdp_mgr.staking_borrow(XRD, borrow_amount, unmatured_claim_nfts, interests);
                 #[cfg(feature = "verbose")]
                 info!("Gase used in claim_xrd cdp_mgr.staking_borrow after: {}", init- Runtime::get_fee_balance());
+                let performance_fee = self.charge_performance_fee(&mut borrow_bucket);
+                xrd_bucket.put(borrow_bucket);
                 Runtime::emit_event(NftFasterRedeemEvent{
                     claim_amount: unmatured_claim_amount,
                     claim_nfts: claim_nft_cnt,
                     xrd_amount: borrow_amount,
+                    performance_fee,
                     current_epoch
                 });
             }
@@ -180,6 +257,7 @@ mod staking_earning {
                 self.staking_pool.get_underlying_token(),
                 bucket.resource_address()
             );
+            self.assert_not_paused(bucket.resource_address(), Operation::Join);
             #[cfg(feature = "verbose")]
             info!("Gase used in join staking_pool.contribute: {}", init- Runtime::get_fee_balance());
             self.staking_pool.contribute(bucket, validator_addr)
@@ -207,6 +285,7 @@ mod staking_earning {
             let init = Runtime::get_fee_balance();
             let res_addr = bucket.resource_address();
             let amount = bucket.amount();
+            self.assert_not_paused(res_addr, Operation::Redeem);
             #[cfg(feature = "verbose")]
             info!("Gase used in redeem 0 : {}", init- Runtime::get_fee_balance());
             let (claim_nft_buckets, xrd_amount) = if res_addr == self.dse_token {
@@ -268,6 +347,8 @@ pub struct NftFasterRedeemEvent{
     pub claim_amount: Decimal,
     pub xrd_amount: Decimal,
     pub claim_nfts: usize,
+    // the part of the XRD amount deposited into the treasury
+    pub performance_fee: Decimal,
     pub current_epoch: u64
 }
 
@@ -278,3 +359,8 @@ pub struct ClaimXrdEvent{
     pub current_epoch: u64
 }
 
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct SetPerformanceFeeEvent{
+    pub treasury: ComponentAddress,
+    pub fee_ratio: Decimal
+}
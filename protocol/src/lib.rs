@@ -1,7 +1,9 @@
 pub mod earning;
 pub mod cdp;
 pub mod pool;
+pub mod treasury;
 
 pub use earning::*;
 pub use cdp::*;
-pub use pool::*;
\ No newline at end of file
+pub use pool::*;
+pub use treasury::*;
\ No newline at end of file
@@ -10,28 +10,136 @@ use interest::InterestModel;
 pub struct FixedEpochBond {
     pub epoch_at: u64,
     pub interest: Decimal,
+    // the principal (claim amount without the interest) of the bonds
+    pub principal: Decimal,
     pub global_id_list: List<NonFungibleGlobalId>
 }
 
+/// The unit of time the interest accrues by.
+#[derive(ScryptoSbor, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccrualMode {
+    // consensus epochs, assuming EPOCH_OF_YEAR epochs per year
+    Epoch,
+    // seconds of the ledger clock
+    Timestamp
+}
+
+/// The accrual points at which a pool migrated from the epoch to the timestamp accrual.
+#[derive(ScryptoSbor, Clone, Copy)]
+pub struct AccrualMigration {
+    pub epoch_at: u64,
+    pub timestamp_at: u64
+}
+
+/// A withdrawal request waiting for the liquidity of the pool.
+#[derive(ScryptoSbor, NonFungibleData)]
+pub struct WithdrawalRequest {
+    pub underlying_token: ResourceAddress,
+    pub requested_epoch: u64,
+    // the redemption value locked at the request, less the claimed part
+    #[mutable]
+    pub amount: Decimal,
+    // the part of the amount reserved in the vault, ready to claim
+    #[mutable]
+    pub filled: Decimal
+}
+
+/// A fixed-term deposit at a fixed rate, redeemable at maturity.
+#[derive(ScryptoSbor, NonFungibleData)]
+pub struct TermDeposit {
+    pub underlying_token: ResourceAddress,
+    pub amount: Decimal,
+    pub fixed_rate: Decimal,
+    // the term deposit index at the deposit, the deposit shares the socialized losses by the index afterwards
+    pub term_index: Decimal,
+    // the accrual points (epochs, or clock seconds in the timestamp mode) of the deposit and the maturity
+    pub start_point: u64,
+    pub maturity_point: u64
+}
+
+/// The principal (normalized by the term deposit index) of the fixed-term deposits maturing at the same accrual point and their average fixed rate.
+#[derive(ScryptoSbor)]
+pub struct TermMaturity {
+    pub amount: Decimal,
+    pub interest_rate: Decimal
+}
+
+/// A checkpoint of the indices and the rates of the pool.
+#[derive(ScryptoSbor, Clone)]
+pub struct IndexCheckpoint {
+    pub epoch_at: u64,
+    // seconds of the ledger clock
+    pub timestamp: u64,
+    pub deposit_index: Decimal,
+    pub loan_index: Decimal,
+    // total borrow / total supply
+    pub utilization: Decimal,
+    pub deposit_interest_rate: Decimal,
+    pub variable_loan_interest_rate: Decimal,
+    pub stable_loan_interest_rate: Decimal
+}
+
 impl FixedEpochBond {
     pub fn clear(&mut self) {
         // self.epoch_at = 0;
         self.interest = Decimal::ZERO;
+        self.principal = Decimal::ZERO;
         self.global_id_list.clear();
     }
 }
 
+/// (to_index / from_index - 1) * SECONDS_OF_YEAR / elapsed_seconds
+fn annualize_index_growth(from_index: Decimal, to_index: Decimal, elapsed_seconds: u64) -> Decimal{
+    if from_index == Decimal::ZERO {
+        return Decimal::ZERO;
+    }
+    to_index.checked_div(from_index).unwrap().checked_sub(Decimal::ONE).unwrap()
+        .checked_mul(Decimal::from(SECONDS_OF_YEAR)).unwrap()
+        .checked_div(Decimal::from(elapsed_seconds)).unwrap()
+}
+
+/// Removes `amount` at `rate` from `total` at the average rate `total_rate`, returns the rest and its average rate.
+fn reduce_weight_rate(total: Decimal, total_rate: Decimal, amount: Decimal, rate: Decimal) -> (Decimal, Decimal){
+    if amount >= total {
+        return (Decimal::ZERO, Decimal::ZERO);
+    }
+    let rest = total.checked_sub(amount).unwrap();
+    let rest_rate = total.checked_mul(total_rate).unwrap().checked_sub(
+        amount.checked_mul(rate).unwrap()
+    ).unwrap().checked_div(rest).unwrap();
+    (rest, rest_rate)
+}
+
 #[blueprint]
 #[types(
     ListIndex,
     NonFungibleGlobalId,
     ResourceAddress,
     NonFungibleVault,
-    FixedEpochBond
+    FixedEpochBond,
+    IndexCheckpoint,
+    TermMaturity
+)]
+#[events(
+    AddLiquidityEvent, RemoveLiquidityEvent, WithdrawInsuranceEvent, AddBondEvent, ClaimBondEvent, UpdateIndexEvent,
+    RequestWithdrawalEvent, FillWithdrawalEvent, ClaimWithdrawalEvent, CancelWithdrawalEvent,
+    DepositTermEvent, RedeemTermDepositEvent
 )]
 mod lend_pool {
 
     const INTEREST_COMPONENT: ComponentAddress = _INTEREST_COMPONENT;
+    // an index checkpoint is recorded at most once an hour
+    const INDEX_CHECKPOINT_EPOCHS: u64 = 12;
+    // the checkpoints of the last 32 days are kept
+    const INDEX_HISTORY_SIZE: u64 = 768;
+    // the maximum number of withdrawal requests filled at a time
+    const WITHDRAWAL_FILL_LIMIT: u64 = 10;
+    // the maximum number of matured claim NFTs claimed by an index update
+    const BOND_CLAIM_LIMIT: u64 = 5;
+    // the terms (in weeks) of the fixed-term deposits
+    const TERM_DEPOSIT_WEEKS: [u64; 3] = [1, 4, 12];
+    // the penalty of the principal for the early exit of a fixed-term deposit
+    const TERM_DEPOSIT_PENALTY_PERCENT: u64 = 1;
 
     extern_blueprint! {
         INTEREST_PACKAGE,
@@ -54,6 +162,13 @@ mod lend_pool {
         methods {
             //operator
             withdraw_insurance => restrict_to: [operator];
+            withdraw_treasury => restrict_to: [operator];
+            set_flashloan_fee_split => restrict_to: [operator];
+            set_paused => restrict_to: [operator];
+            set_interest_model => restrict_to: [operator];
+            set_insurance_ratio => restrict_to: [operator];
+            set_flashloan_fee_ratio => restrict_to: [operator];
+            set_accrual_mode => restrict_to: [operator];
             borrow_variable => restrict_to: [operator];
             borrow_stable => restrict_to: [operator];
             repay_stable => restrict_to: [operator];
@@ -61,10 +176,22 @@ mod lend_pool {
             borrow_fixed_term => restrict_to:[operator];
             repay_fixed_term => restrict_to:[operator];
             add_fixed_term => restrict_to:[operator];
+            swap_to_variable => restrict_to:[operator];
+            swap_to_stable => restrict_to:[operator];
+            rebalance_stable => restrict_to:[operator];
+            write_off_variable => restrict_to:[operator];
+            write_off_stable => restrict_to:[operator];
+            redeem_collateral => restrict_to:[operator];
             
             //business method
             add_liquity => PUBLIC;
             remove_liquity => PUBLIC; 
+            request_withdrawal => PUBLIC;
+            claim_withdrawal => PUBLIC;
+            cancel_withdrawal => PUBLIC;
+            deposit_term => PUBLIC;
+            redeem_term_deposit => PUBLIC;
+            process_matured_bonds => PUBLIC;
 
             // readonly
             get_current_index => PUBLIC;
@@ -74,6 +201,13 @@ mod lend_pool {
             get_redemption_value => PUBLIC;
             get_flashloan_fee_ratio => PUBLIC;
             get_variable_interest => PUBLIC;
+            get_total_supply_borrow => PUBLIC;
+            is_paused => PUBLIC;
+            get_accrual_point => PUBLIC;
+            get_accrual_mode => PUBLIC;
+            get_index_history => PUBLIC;
+            get_trailing_apy => PUBLIC;
+            get_term_deposit_rate => PUBLIC;
         }
     }
     
@@ -95,10 +229,18 @@ mod lend_pool {
         deposit_index: Decimal,
         loan_index: Decimal,
         
+        // the accrual point (epoch or clock seconds, depending on the accrual mode) of the last index update
         last_update: u64,
+        accrual_mode: AccrualMode,
+        // the stable loans recorded before the migration still hold accrual points in epochs.
+        accrual_migration: Option<AccrualMigration>,
 
         insurance_ratio: Decimal,
         flashloan_fee_ratio: Decimal,
+        // the shares of the flash loan fee for insurance and treasury, the rest goes to the depositors.
+        flashloan_insurance_ratio: Decimal,
+        flashloan_treasury_ratio: Decimal,
+        treasury_balance: Decimal,
         
         deposit_interest_rate: Decimal,
         
@@ -113,6 +255,45 @@ mod lend_pool {
         bonds: KeyValueStore<u64, FixedEpochBond>,
         claim_nfts: NonFungibleVaults,
         bond_amount: Decimal,
+        // the position in the `global_id_list` of the first matured epoch up to which the claim NFTs are claimed
+        bond_cursor: u64,
+
+        // the operations paused by the guardian
+        paused_operations: HashSet<Operation>,
+
+        // ring buffer of the index checkpoints, only the latest INDEX_HISTORY_SIZE ones are kept
+        index_history: List<IndexCheckpoint>,
+        // the number of checkpoints ever recorded
+        index_checkpoint_count: u64,
+        last_checkpoint_epoch: u64,
+
+        // withdrawal request NFT define
+        withdrawal_res_mgr: NonFungibleResourceManager,
+        // withdrawal request id counter
+        withdrawal_id_counter: u64,
+        // ids of the withdrawal requests in the order of request, the ones before the head are filled or cancelled.
+        withdrawal_queue: List<u64>,
+        withdrawal_queue_head: u64,
+        // the unfilled amount of the withdrawal requests
+        queued_withdrawal: Decimal,
+        // the amount in the vault reserved for the filled withdrawal requests
+        withdrawal_reserved: Decimal,
+
+        // term deposit NFT define
+        term_deposit_res_mgr: NonFungibleResourceManager,
+        // term deposit id counter
+        term_deposit_id_counter: u64,
+        // the value of a unit of the fixed-term deposits, reduced by their share of the socialized losses
+        term_deposit_index: Decimal,
+        // the principal (normalized by the term deposit index) of the fixed-term deposits accruing the fixed interest
+        term_deposit_amount: Decimal,
+        // the average fixed rate of the accruing fixed-term deposits
+        term_deposit_interest_rate: Decimal,
+        // the normalized principal of the matured fixed-term deposits waiting for the redemption, they accrue no more interest
+        term_deposit_matured: Decimal,
+        // the maturity points of the accruing fixed-term deposits in ascending order
+        term_maturity_points: Vec<u64>,
+        term_maturities: KeyValueStore<u64, TermMaturity>,
     }
 
 
@@ -154,10 +335,52 @@ mod lend_pool {
                 })
                 .create_with_no_initial_supply();
 
+            let withdrawal_res_mgr = ResourceBuilder::new_integer_non_fungible::<WithdrawalRequest>(OwnerRole::None)
+                .metadata(metadata!(init{
+                    "pool" => address, locked;
+                    "underlying" => underlying_token, locked;
+                    "name" => format!("DeXian Withdrawal Request({})", origin_symbol), locked;
+                    "icon_url" => "https://dexian.io/images/dx.png", updatable;
+                    "info_url" => "https://dexian.io", updatable;
+                }))
+                .mint_roles(mint_roles! {
+                    minter => dx_rule.clone();
+                    minter_updater => rule!(deny_all);
+                })
+                .burn_roles(burn_roles! {
+                    burner => dx_rule.clone();
+                    burner_updater => rule!(deny_all);
+                })
+                .non_fungible_data_update_roles(non_fungible_data_update_roles! {
+                    non_fungible_data_updater => dx_rule.clone();
+                    non_fungible_data_updater_updater => rule!(deny_all);
+                })
+                .create_with_no_initial_supply();
+
+            let term_deposit_res_mgr = ResourceBuilder::new_integer_non_fungible::<TermDeposit>(OwnerRole::None)
+                .metadata(metadata!(init{
+                    "pool" => address, locked;
+                    "underlying" => underlying_token, locked;
+                    "name" => format!("DeXian Term Deposit({})", origin_symbol), locked;
+                    "icon_url" => "https://dexian.io/images/dx.png", updatable;
+                    "info_url" => "https://dexian.io", updatable;
+                }))
+                .mint_roles(mint_roles! {
+                    minter => dx_rule.clone();
+                    minter_updater => rule!(deny_all);
+                })
+                .burn_roles(burn_roles! {
+                    burner => dx_rule.clone();
+                    burner_updater => rule!(deny_all);
+                })
+                .create_with_no_initial_supply();
+
             let component = Self {
                 deposit_index: Decimal::ONE,
                 loan_index: Decimal::ONE,
                 last_update: 0u64,
+                accrual_mode: AccrualMode::Epoch,
+                accrual_migration: None,
                 deposit_interest_rate: Decimal::ZERO,
                 variable_loan_interest_rate: Decimal::ZERO,
                 variable_loan_share_quantity: Decimal::ZERO,
@@ -166,10 +389,32 @@ mod lend_pool {
                 stable_loan_last_update: 0u64,
                 vault: FungibleVault::new(underlying_token),
                 insurance_balance: Decimal::ZERO,
+                flashloan_insurance_ratio: insurance_ratio,
+                flashloan_treasury_ratio: Decimal::ZERO,
+                treasury_balance: Decimal::ZERO,
                 bond_epochs: Vec::new(),
                 bonds: KeyValueStore::new(),
                 claim_nfts: NonFungibleVaults::new(|| LendResourcePoolKeyValueStore::new_with_registered_type()),
                 bond_amount: Decimal::ZERO,
+                bond_cursor: 0u64,
+                paused_operations: HashSet::new(),
+                index_history: List::new(|| LendResourcePoolKeyValueStore::new_with_registered_type()),
+                index_checkpoint_count: 0u64,
+                last_checkpoint_epoch: 0u64,
+                withdrawal_res_mgr,
+                withdrawal_id_counter: 0u64,
+                withdrawal_queue: List::new(|| LendResourcePoolKeyValueStore::new_with_registered_type()),
+                withdrawal_queue_head: 0u64,
+                queued_withdrawal: Decimal::ZERO,
+                withdrawal_reserved: Decimal::ZERO,
+                term_deposit_res_mgr,
+                term_deposit_id_counter: 0u64,
+                term_deposit_index: Decimal::ONE,
+                term_deposit_amount: Decimal::ZERO,
+                term_deposit_interest_rate: Decimal::ZERO,
+                term_deposit_matured: Decimal::ZERO,
+                term_maturity_points: Vec::new(),
+                term_maturities: KeyValueStore::new(),
                 interest_model,
                 insurance_ratio,
                 underlying_token,
@@ -192,9 +437,159 @@ mod lend_pool {
 
         pub fn withdraw_insurance(&mut self, amount: Decimal) -> FungibleBucket{
             assert_amount(amount, self.insurance_balance);
+            assert!(amount <= self.get_available_liquidity(), "the balance in vault is insufficient.");
+            self.insurance_balance = self.insurance_balance.checked_sub(amount).unwrap();
+            let bucket = self.vault.take_advanced(amount, TO_ZERO);
+            Runtime::emit_event(WithdrawInsuranceEvent{
+                underlying_token: self.underlying_token,
+                amount: bucket.amount(),
+                insurance_balance: self.insurance_balance
+            });
+            bucket
+        }
+
+        pub fn withdraw_treasury(&mut self, amount: Decimal) -> FungibleBucket{
+            assert!(amount <= self.treasury_balance, "the treasury balance is insufficient.");
+            assert!(amount <= self.get_available_liquidity(), "the balance in vault is insufficient.");
+            self.treasury_balance = self.treasury_balance.checked_sub(amount).unwrap();
             self.vault.take_advanced(amount, TO_ZERO)
         }
 
+        pub fn set_flashloan_fee_split(&mut self, insurance_ratio: Decimal, treasury_ratio: Decimal){
+            assert!(
+                !insurance_ratio.is_negative() && !treasury_ratio.is_negative() && insurance_ratio.checked_add(treasury_ratio).unwrap() <= Decimal::ONE,
+                "Incorrect flash loan fee split!"
+            );
+            self.update_index();
+            self.flashloan_insurance_ratio = insurance_ratio;
+            self.flashloan_treasury_ratio = treasury_ratio;
+        }
+
+        pub fn set_interest_model(&mut self, interest_model: InterestModel){
+            // accrue the interest with the previous model first.
+            self.update_index();
+            self.interest_model = interest_model;
+            self.update_interest_rate();
+        }
+
+        pub fn set_insurance_ratio(&mut self, insurance_ratio: Decimal){
+            self.update_index();
+            self.insurance_ratio = insurance_ratio;
+            self.update_interest_rate();
+        }
+
+        pub fn set_flashloan_fee_ratio(&mut self, flashloan_fee_ratio: Decimal){
+            self.update_index();
+            self.flashloan_fee_ratio = flashloan_fee_ratio;
+        }
+
+        ///
+        /// Migrates the interest accrual from epochs to clock seconds. The indices accrue up to the current epoch first,
+        /// then the accrual points of the pool are converted. The stable loans recorded in epochs before the migration
+        /// accrue by epochs up to the migration and by seconds afterwards.
+        pub fn set_accrual_mode(&mut self, accrual_mode: AccrualMode){
+            if accrual_mode == self.accrual_mode {
+                return;
+            }
+            assert!(accrual_mode == AccrualMode::Timestamp, "The accrual mode can only be migrated from epoch to timestamp!");
+            self.update_index();
+            let migration = AccrualMigration{
+                epoch_at: Runtime::current_epoch().number(),
+                timestamp_at: Self::current_timestamp()
+            };
+            self.last_update = migration.timestamp_at;
+            self.accrual_migration = Some(migration);
+            self.accrual_mode = accrual_mode;
+            self.stable_loan_last_update = self.to_accrual_point(self.stable_loan_last_update);
+
+            // the term deposits yet to mature are tracked by the maturities in seconds from now on.
+            for point in std::mem::take(&mut self.term_maturity_points) {
+                if let Some(maturity) = self.term_maturities.remove(&point) {
+                    let maturity_point = self.to_accrual_point(point);
+                    self.term_maturities.insert(maturity_point, maturity);
+                    self.term_maturity_points.push(maturity_point);
+                }
+            }
+            self.update_interest_rate();
+        }
+
+        pub fn get_accrual_mode(&self) -> AccrualMode{
+            self.accrual_mode
+        }
+
+        ///
+        /// Returns the current accrual point: the epoch number, or the seconds of the clock in the timestamp mode.
+        /// The stable loans record it to accrue the interest afterwards.
+        pub fn get_accrual_point(&self) -> u64{
+            match self.accrual_mode {
+                AccrualMode::Epoch => Runtime::current_epoch().number(),
+                AccrualMode::Timestamp => Self::current_timestamp()
+            }
+        }
+
+        fn current_timestamp() -> u64{
+            Clock::current_time_rounded_to_seconds().seconds_since_unix_epoch as u64
+        }
+
+        fn get_periods_of_year(&self) -> Decimal{
+            match self.accrual_mode {
+                AccrualMode::Epoch => Decimal::from(EPOCH_OF_YEAR),
+                AccrualMode::Timestamp => Decimal::from(SECONDS_OF_YEAR)
+            }
+        }
+
+        fn get_periods_of_day(&self) -> u64{
+            match self.accrual_mode {
+                AccrualMode::Epoch => EPOCH_OF_YEAR / 365,
+                AccrualMode::Timestamp => SECONDS_OF_YEAR / 365
+            }
+        }
+
+        /// Converts an accrual point recorded in epochs before the migration into clock seconds.
+        fn to_accrual_point(&self, point: u64) -> u64{
+            match self.accrual_migration {
+                Some(migration) if point < migration.timestamp_at => {
+                    let epoch_seconds = SECONDS_OF_YEAR / EPOCH_OF_YEAR;
+                    if point >= migration.epoch_at {
+                        migration.timestamp_at + (point - migration.epoch_at) * epoch_seconds
+                    } else {
+                        migration.timestamp_at.saturating_sub((migration.epoch_at - point) * epoch_seconds)
+                    }
+                },
+                _ => point
+            }
+        }
+
+        /// Returns (1+rate/periods_of_year)^periods from the accrual point `last_point` to now.
+        fn calc_compound_rate_since(&self, rate: Decimal, last_point: u64) -> Decimal{
+            let current_point = self.get_accrual_point();
+            match self.accrual_migration {
+                Some(migration) if last_point < migration.timestamp_at => {
+                    // the accrual point was recorded in epochs before the migration.
+                    calc_compound_rate(rate, Decimal::from(EPOCH_OF_YEAR), migration.epoch_at.saturating_sub(last_point)).checked_mul(
+                        calc_compound_rate(rate, Decimal::from(SECONDS_OF_YEAR), current_point - migration.timestamp_at)
+                    ).unwrap()
+                },
+                _ => calc_compound_rate(rate, self.get_periods_of_year(), current_point.saturating_sub(last_point))
+            }
+        }
+
+        pub fn set_paused(&mut self, operation: Operation, paused: bool){
+            if paused {
+                self.paused_operations.insert(operation);
+            } else {
+                self.paused_operations.remove(&operation);
+            }
+        }
+
+        pub fn is_paused(&self, operation: Operation) -> bool{
+            self.paused_operations.contains(&operation)
+        }
+
+        fn assert_not_paused(&self, operation: Operation){
+            assert!(!self.is_paused(operation), "The operation {:?} of the pool is paused!", operation);
+        }
+
         // pub fn get_underlying_value(&self) -> Decimal{
         //     let (supply_index, _) = self.get_current_index();
         //     self.deposit_share_res_mgr.total_supply().unwrap().checked_mul(supply_index).unwrap()
@@ -202,6 +597,7 @@ mod lend_pool {
 
         pub fn add_liquity(&mut self, bucket: FungibleBucket) -> FungibleBucket{
             assert_resource(&bucket.resource_address(), &self.underlying_token);
+            self.assert_not_paused(Operation::Supply);
             let deposit_amount = bucket.amount();
 
             self.update_index();
@@ -211,6 +607,13 @@ mod lend_pool {
             let divisibility = self.deposit_share_res_mgr.resource_type().divisibility().unwrap();
             let mint_amount = floor(deposit_amount.checked_div(self.deposit_index).unwrap(), divisibility);
             let dx_bucket = self.deposit_share_res_mgr.mint(mint_amount);
+            Runtime::emit_event(AddLiquidityEvent{
+                underlying_token: self.underlying_token,
+                amount: deposit_amount,
+                dx_amount: mint_amount,
+                deposit_index: self.deposit_index
+            });
+            self.fill_withdrawal_queue();
             
             info!("after interest rate:{}, {}, index:{}, {}", self.variable_loan_interest_rate, self.stable_loan_interest_rate, self.deposit_index, self.loan_index);
             self.update_interest_rate();
@@ -220,14 +623,39 @@ mod lend_pool {
         }
         pub fn remove_liquity(&mut self, bucket: FungibleBucket) -> FungibleBucket{
             assert_resource(&bucket.resource_address(), &self.deposit_share_res_mgr.address());
+            self.assert_not_paused(Operation::Withdraw);
+
+            self.update_index();
+            self.fill_withdrawal_queue();
+            assert!(
+                self.withdrawal_queue_head >= self.withdrawal_queue.len(),
+                "The withdrawal requests are waiting for the liquidity, use request_withdrawal instead."
+            );
+            self.redeem(bucket)
+        }
+
+        ///
+        /// Redeems the dx tokens released from a CDP. Unlike `remove_liquity` it ignores the withdraw pause,
+        /// so liquidations and closing positions keep working while the pool is paused.
+        pub fn redeem_collateral(&mut self, bucket: FungibleBucket) -> FungibleBucket{
+            assert_resource(&bucket.resource_address(), &self.deposit_share_res_mgr.address());
+            self.redeem(bucket)
+        }
 
+        fn redeem(&mut self, bucket: FungibleBucket) -> FungibleBucket{
             self.update_index();
 
             let burn_amount = bucket.amount();
             let divisibility = get_divisibility(self.underlying_token).unwrap();
             let withdraw_amount = floor(self.get_redemption_value(burn_amount), divisibility);
-            assert!(self.vault.amount() >= withdraw_amount, "the balance in vault is insufficient.");
+            assert!(self.get_fillable_liquidity() >= withdraw_amount, "the balance in vault is insufficient.");
             self.deposit_share_res_mgr.burn(bucket);
+            Runtime::emit_event(RemoveLiquidityEvent{
+                underlying_token: self.underlying_token,
+                amount: withdraw_amount,
+                dx_amount: burn_amount,
+                deposit_index: self.deposit_index
+            });
 
             info!("after interest rate:{}, {}, index:{}, {}", self.variable_loan_interest_rate, self.stable_loan_interest_rate, self.deposit_index, self.loan_index);
             self.update_interest_rate();
@@ -237,8 +665,336 @@ mod lend_pool {
 
         }
 
+        ///
+        /// Burns the dx tokens into a withdrawal request NFT when the vault is short of liquidity.
+        /// The redemption value is locked at the current deposit index and no longer accrues interest,
+        /// the requests are filled FIFO as repayments and matured bonds flow into the vault.
+        pub fn request_withdrawal(&mut self, bucket: FungibleBucket) -> NonFungibleBucket{
+            assert_resource(&bucket.resource_address(), &self.deposit_share_res_mgr.address());
+            self.assert_not_paused(Operation::Withdraw);
+
+            self.update_index();
+
+            let burn_amount = bucket.amount();
+            let divisibility = get_divisibility(self.underlying_token).unwrap();
+            let amount = floor(self.get_redemption_value(burn_amount), divisibility);
+            assert!(amount.is_positive(), "The amount of the withdrawal request must be positive!");
+            self.deposit_share_res_mgr.burn(bucket);
+
+            self.withdrawal_id_counter += 1;
+            let request_id = NonFungibleLocalId::integer(self.withdrawal_id_counter);
+            let request = self.withdrawal_res_mgr.mint_non_fungible(&request_id, WithdrawalRequest{
+                underlying_token: self.underlying_token,
+                requested_epoch: Runtime::current_epoch().number(),
+                amount,
+                filled: Decimal::ZERO
+            });
+            self.withdrawal_queue.push(self.withdrawal_id_counter);
+            self.queued_withdrawal = self.queued_withdrawal.checked_add(amount).unwrap();
+            Runtime::emit_event(RequestWithdrawalEvent{
+                underlying_token: self.underlying_token,
+                request_id,
+                amount,
+                dx_amount: burn_amount,
+                deposit_index: self.deposit_index
+            });
+            self.fill_withdrawal_queue();
+
+            self.update_interest_rate();
+            request
+        }
+
+        ///
+        /// Pays out the filled part of the withdrawal request. The request is burnt once it is fully paid,
+        /// otherwise it is returned to wait for the rest.
+        pub fn claim_withdrawal(&mut self, request: NonFungibleBucket) -> (FungibleBucket, Option<NonFungibleBucket>){
+            assert_resource(&request.resource_address(), &self.withdrawal_res_mgr.address());
+            assert!(request.amount() == Decimal::ONE, "Only one withdrawal request can be processed at a time!");
+            self.assert_not_paused(Operation::Withdraw);
+
+            self.update_index();
+            self.fill_withdrawal_queue();
+
+            let request_id = request.non_fungible_local_id();
+            let data = self.withdrawal_res_mgr.get_non_fungible_data::<WithdrawalRequest>(&request_id);
+            let bucket = self.vault.take_advanced(data.filled, TO_ZERO);
+            self.withdrawal_reserved = self.withdrawal_reserved.checked_sub(data.filled).unwrap();
+            let remaining = data.amount.checked_sub(data.filled).unwrap();
+            Runtime::emit_event(ClaimWithdrawalEvent{
+                underlying_token: self.underlying_token,
+                request_id: request_id.clone(),
+                amount: data.filled,
+                remaining
+            });
+
+            if remaining == Decimal::ZERO {
+                self.withdrawal_res_mgr.burn(request);
+                (bucket, None)
+            } else {
+                self.withdrawal_res_mgr.update_non_fungible_data(&request_id, "amount", remaining);
+                self.withdrawal_res_mgr.update_non_fungible_data(&request_id, "filled", Decimal::ZERO);
+                (bucket, Some(request))
+            }
+        }
+
+        ///
+        /// Cancels the withdrawal request, the filled part is paid out and the unfilled part is deposited back
+        /// as dx tokens at the current deposit index.
+        pub fn cancel_withdrawal(&mut self, request: NonFungibleBucket) -> (FungibleBucket, FungibleBucket){
+            assert_resource(&request.resource_address(), &self.withdrawal_res_mgr.address());
+            assert!(request.amount() == Decimal::ONE, "Only one withdrawal request can be processed at a time!");
+
+            self.update_index();
+
+            let request_id = request.non_fungible_local_id();
+            let data = self.withdrawal_res_mgr.get_non_fungible_data::<WithdrawalRequest>(&request_id);
+            let bucket = self.vault.take_advanced(data.filled, TO_ZERO);
+            self.withdrawal_reserved = self.withdrawal_reserved.checked_sub(data.filled).unwrap();
+            let unfilled = data.amount.checked_sub(data.filled).unwrap();
+            self.queued_withdrawal = self.queued_withdrawal.checked_sub(unfilled).unwrap();
+
+            let divisibility = self.deposit_share_res_mgr.resource_type().divisibility().unwrap();
+            let dx_amount = floor(unfilled.checked_div(self.deposit_index).unwrap(), divisibility);
+            let dx_bucket = self.deposit_share_res_mgr.mint(dx_amount);
+            self.withdrawal_res_mgr.burn(request);
+            Runtime::emit_event(CancelWithdrawalEvent{
+                underlying_token: self.underlying_token,
+                request_id,
+                amount: data.filled,
+                dx_amount,
+                deposit_index: self.deposit_index
+            });
+
+            self.update_interest_rate();
+            (bucket, dx_bucket)
+        }
+
+        ///
+        /// Deposits for a term of 1, 4 or 12 weeks at the quoted fixed rate, see `get_term_deposit_rate`.
+        /// The maturity is rounded up to the end of the day.
+        /// Returns the term deposit NFT, which is redeemable for the principal and the fixed interest at maturity.
+        pub fn deposit_term(&mut self, bucket: FungibleBucket, weeks: u64) -> NonFungibleBucket{
+            assert_resource(&bucket.resource_address(), &self.underlying_token);
+            assert!(TERM_DEPOSIT_WEEKS.contains(&weeks), "The term must be 1, 4 or 12 weeks!");
+            self.assert_not_paused(Operation::Supply);
+
+            self.update_index();
+
+            let amount = bucket.amount();
+            assert!(amount.is_positive(), "The amount of the term deposit must be positive!");
+            let fixed_rate = self.get_term_deposit_rate(amount);
+            self.vault.put(bucket);
+            let normalized_amount = amount.checked_div(self.term_deposit_index).unwrap();
+            self.term_deposit_interest_rate = get_weight_rate(self.term_deposit_amount, self.term_deposit_interest_rate, normalized_amount, fixed_rate);
+            self.term_deposit_amount = self.term_deposit_amount.checked_add(normalized_amount).unwrap();
+
+            let start_point = self.get_accrual_point();
+            let day = self.get_periods_of_day();
+            let maturity_point = start_point.checked_add(weeks * 7 * day).unwrap().div_ceil(day) * day;
+            self.add_term_maturity(maturity_point, normalized_amount, fixed_rate);
+            self.term_deposit_id_counter += 1;
+            let deposit_id = NonFungibleLocalId::integer(self.term_deposit_id_counter);
+            let term_deposit = self.term_deposit_res_mgr.mint_non_fungible(&deposit_id, TermDeposit{
+                underlying_token: self.underlying_token,
+                amount,
+                fixed_rate,
+                term_index: self.term_deposit_index,
+                start_point,
+                maturity_point
+            });
+            Runtime::emit_event(DepositTermEvent{
+                underlying_token: self.underlying_token,
+                deposit_id,
+                amount,
+                fixed_rate,
+                maturity_point
+            });
+            self.fill_withdrawal_queue();
+
+            self.update_interest_rate();
+            term_deposit
+        }
+
+        ///
+        /// Redeems the term deposit for the principal and the fixed interest, less its share of the socialized losses.
+        /// Before maturity, the interest accrued so far is paid less a penalty of TERM_DEPOSIT_PENALTY_PERCENT of the principal,
+        /// the penalty goes into the insurance.
+        pub fn redeem_term_deposit(&mut self, term_deposit: NonFungibleBucket) -> FungibleBucket{
+            assert_resource(&term_deposit.resource_address(), &self.term_deposit_res_mgr.address());
+            assert!(term_deposit.amount() == Decimal::ONE, "Only one term deposit can be processed at a time!");
+            self.assert_not_paused(Operation::Withdraw);
+
+            self.update_index();
+
+            let deposit_id = term_deposit.non_fungible_local_id();
+            let data = self.term_deposit_res_mgr.get_non_fungible_data::<TermDeposit>(&deposit_id);
+            let current_point = self.get_accrual_point();
+            let start_point = self.to_accrual_point(data.start_point);
+            let maturity_point = self.to_accrual_point(data.maturity_point);
+            let is_matured = current_point >= maturity_point;
+            // the principal less the share of the socialized losses since the deposit
+            let principal = data.amount.checked_mul(self.term_deposit_index).unwrap().checked_div(data.term_index).unwrap();
+            let (accrual_point, penalty) = if is_matured {
+                (maturity_point, Decimal::ZERO)
+            } else {
+                (current_point, floor_by_resource(self.underlying_token, precent_mul(principal, Decimal::from(TERM_DEPOSIT_PENALTY_PERCENT))))
+            };
+            let interest = floor_by_resource(
+                self.underlying_token,
+                principal.checked_mul(
+                    calc_linear_rate(data.fixed_rate, self.get_periods_of_year(), accrual_point.saturating_sub(start_point))
+                ).unwrap()
+            );
+            let payout = floor_by_resource(self.underlying_token, principal).checked_add(interest).unwrap().checked_sub(penalty).unwrap();
+            assert!(self.get_available_liquidity() >= payout, "the balance in vault is insufficient.");
+
+            let normalized_amount = data.amount.checked_div(data.term_index).unwrap();
+            if is_matured {
+                // the deposit matured by the index update above.
+                self.term_deposit_matured = if normalized_amount >= self.term_deposit_matured { Decimal::ZERO } else { self.term_deposit_matured.checked_sub(normalized_amount).unwrap() };
+            } else {
+                self.reduce_term_deposit(normalized_amount, data.fixed_rate);
+                self.reduce_term_maturity(maturity_point, normalized_amount, data.fixed_rate);
+            }
+            self.insurance_balance = self.insurance_balance.checked_add(penalty).unwrap();
+            self.term_deposit_res_mgr.burn(term_deposit);
+            Runtime::emit_event(RedeemTermDepositEvent{
+                underlying_token: self.underlying_token,
+                deposit_id,
+                principal,
+                interest,
+                penalty
+            });
+
+            self.update_interest_rate();
+            self.vault.take_advanced(payout, TO_ZERO)
+        }
+
+        ///
+        /// Quotes the fixed rate of a term deposit of `amount`: the supply rate of the pool after the deposit, derived from the interest model.
+        pub fn get_term_deposit_rate(&self, amount: Decimal) -> Decimal{
+            let (supply_index, variable_borrow_index) = self.get_current_index();
+            let supply: Decimal = self.get_deposit_share_quantity().checked_mul(supply_index).unwrap();
+            let variable_borrow = self.get_variable_share_quantity().checked_mul(variable_borrow_index).unwrap();
+            let stable_borrow = self.get_stable_loan_value();
+
+            let (_, _, supply_rate) = self.calc_interest_rate(supply.checked_add(amount).unwrap(), variable_borrow, stable_borrow);
+            supply_rate
+        }
+
+        ///
+        /// Claims at most `max_nfts` claim NFTs of the matured unstake bonds besides the ones claimed by the index update.
+        /// Anyone can call it to keep the bonds settled. Returns the number of the NFTs claimed by this call.
+        pub fn process_matured_bonds(&mut self, max_nfts: u64) -> u64{
+            self.update_index();
+            let claimed = self.claim_matured_bonds(max_nfts);
+            self.update_interest_rate();
+            claimed
+        }
+
+        /// Removes the principal of an accruing term deposit from the pool and re-weights the average fixed rate.
+        fn reduce_term_deposit(&mut self, amount: Decimal, rate: Decimal){
+            (self.term_deposit_amount, self.term_deposit_interest_rate) = reduce_weight_rate(
+                self.term_deposit_amount, self.term_deposit_interest_rate, amount, rate
+            );
+        }
+
+        fn add_term_maturity(&mut self, maturity_point: u64, amount: Decimal, rate: Decimal){
+            if let Some(mut maturity) = self.term_maturities.get_mut(&maturity_point) {
+                maturity.interest_rate = get_weight_rate(maturity.amount, maturity.interest_rate, amount, rate);
+                maturity.amount = maturity.amount.checked_add(amount).unwrap();
+                return;
+            }
+            if let Err(index) = self.term_maturity_points.binary_search(&maturity_point) {
+                self.term_maturity_points.insert(index, maturity_point);
+            }
+            self.term_maturities.insert(maturity_point, TermMaturity{ amount, interest_rate: rate });
+        }
+
+        fn reduce_term_maturity(&mut self, maturity_point: u64, amount: Decimal, rate: Decimal){
+            if let Some(mut maturity) = self.term_maturities.get_mut(&maturity_point) {
+                (maturity.amount, maturity.interest_rate) = reduce_weight_rate(maturity.amount, maturity.interest_rate, amount, rate);
+            }
+        }
+
+        ///
+        /// Accrues the fixed interest of the term deposits from the last update to `current_point`.
+        /// The deposits stop accruing at their maturity, their principal waits for the redemption as matured.
+        /// Returns the accrued interest.
+        fn accrue_term_deposits(&mut self, current_point: u64) -> Decimal{
+            let periods_of_year = self.get_periods_of_year();
+            let mut last_point = self.last_update;
+            let mut interest = Decimal::ZERO;
+            while let Some(maturity_point) = self.term_maturity_points.first().cloned() {
+                if maturity_point > current_point {
+                    break;
+                }
+                interest = interest.checked_add(self.get_accruing_term_deposit().checked_mul(
+                    calc_linear_rate(self.term_deposit_interest_rate, periods_of_year, maturity_point.saturating_sub(last_point))
+                ).unwrap()).unwrap();
+                if let Some(maturity) = self.term_maturities.remove(&maturity_point) {
+                    self.reduce_term_deposit(maturity.amount, maturity.interest_rate);
+                    self.term_deposit_matured = self.term_deposit_matured.checked_add(maturity.amount).unwrap();
+                }
+                self.term_maturity_points.remove(0);
+                last_point = last_point.max(maturity_point);
+            }
+            interest.checked_add(self.get_accruing_term_deposit().checked_mul(
+                calc_linear_rate(self.term_deposit_interest_rate, periods_of_year, current_point.saturating_sub(last_point))
+            ).unwrap()).unwrap()
+        }
+
+        /// Reserves the free liquidity of the vault for the queued withdrawal requests, from the oldest one.
+        fn fill_withdrawal_queue(&mut self){
+            let mut processed = 0u64;
+            while self.withdrawal_queue_head < self.withdrawal_queue.len() && processed < WITHDRAWAL_FILL_LIMIT {
+                processed += 1;
+                let request_id = NonFungibleLocalId::integer(*self.withdrawal_queue.get(self.withdrawal_queue_head).unwrap());
+                // the request has been cancelled.
+                if !self.withdrawal_res_mgr.non_fungible_exists(&request_id) {
+                    self.withdrawal_queue_head += 1;
+                    continue;
+                }
+                let available = self.get_fillable_liquidity();
+                if available == Decimal::ZERO {
+                    break;
+                }
+                let data = self.withdrawal_res_mgr.get_non_fungible_data::<WithdrawalRequest>(&request_id);
+                let unfilled = data.amount.checked_sub(data.filled).unwrap();
+                let fill_amount = if unfilled > available { available } else { unfilled };
+                self.withdrawal_res_mgr.update_non_fungible_data(&request_id, "filled", data.filled.checked_add(fill_amount).unwrap());
+                self.withdrawal_reserved = self.withdrawal_reserved.checked_add(fill_amount).unwrap();
+                self.queued_withdrawal = self.queued_withdrawal.checked_sub(fill_amount).unwrap();
+                Runtime::emit_event(FillWithdrawalEvent{
+                    underlying_token: self.underlying_token,
+                    request_id,
+                    amount: fill_amount,
+                    unfilled: unfilled.checked_sub(fill_amount).unwrap()
+                });
+                if fill_amount < unfilled {
+                    break;
+                }
+                self.withdrawal_queue_head += 1;
+            }
+        }
+
+        /// The balance of the vault less the part reserved for the filled withdrawal requests.
+        fn get_available_liquidity(&self) -> Decimal{
+            self.vault.amount().checked_sub(self.withdrawal_reserved).unwrap()
+        }
+
+        /// The available liquidity that belongs to the depositors, the insurance and treasury balances are not lent out,
+        /// withdrawn by the depositors nor used to fill the queue.
+        fn get_fillable_liquidity(&self) -> Decimal{
+            let fillable = self.get_available_liquidity()
+                .checked_sub(self.insurance_balance).unwrap()
+                .checked_sub(self.treasury_balance).unwrap();
+            if fillable.is_positive() { fillable } else { Decimal::ZERO }
+        }
+
         pub fn borrow_variable(&mut self, borrow_amount: Decimal) -> (FungibleBucket, Decimal){
-            assert!(self.vault.amount() >= borrow_amount, "the balance in vault is insufficient.");
+            self.assert_not_paused(Operation::Borrow);
+            assert!(self.get_fillable_liquidity() >= borrow_amount, "the balance in vault is insufficient.");
             
             self.update_index();
             
@@ -254,7 +1010,8 @@ mod lend_pool {
         }
 
         pub fn borrow_stable(&mut self, borrow_amount: Decimal, stable_rate: Decimal) -> FungibleBucket{
-            assert!(self.vault.amount() >= borrow_amount, "the balance in vault is insufficient.");
+            self.assert_not_paused(Operation::Borrow);
+            assert!(self.get_fillable_liquidity() >= borrow_amount, "the balance in vault is insufficient.");
 
             self.update_index();
 
@@ -295,6 +1052,7 @@ mod lend_pool {
             
             self.variable_loan_share_quantity = self.variable_loan_share_quantity.checked_sub(normalized).unwrap();
             self.vault.put(repay_bucket.take(actual_amount));
+            self.fill_withdrawal_queue();
             
             self.update_interest_rate();
             
@@ -309,35 +1067,17 @@ mod lend_pool {
             last_epoch_at: u64,
             repay_opt: Option<Decimal>
         ) -> (FungibleBucket, Decimal, Decimal, Decimal, u64){
-            let current_epoch_at = Runtime::current_epoch().number();
-            let delta_epoch = current_epoch_at - last_epoch_at;
-            let interest = if delta_epoch <= 0u64 {
-                Decimal::ZERO
-            } else { 
-                ceil_by_resource(
-                    self.underlying_token, 
-                    calc_compound_interest(
-                        loan_amount,
-                        rate,
-                        Decimal::from(EPOCH_OF_YEAR),
-                        delta_epoch
-                    ).checked_sub(loan_amount).unwrap()
-                )
-            };
-            
-            let previous_debt = self.stable_loan_amount.checked_mul(self.stable_loan_interest_rate).unwrap();
+            self.update_index();
+
+            let current_point = self.get_accrual_point();
+            let interest = self.calc_stable_interest(loan_amount, rate, last_epoch_at);
 
             let mut repay_amount = if repay_opt.is_some_and(|uplimit|uplimit<repay_bucket.amount()){ repay_opt.unwrap() } else { repay_bucket.amount() };
             let repay_in_borrow: Decimal;
             if repay_amount < interest {
+                // the outstanding interest is capitalized, the pool has accrued it into the stable loans already.
                 let outstanding_interest = interest.checked_sub(repay_amount).unwrap();
                 repay_in_borrow = outstanding_interest.checked_mul(Decimal::from(-1)).unwrap();
-                self.stable_loan_amount = self.stable_loan_amount.checked_add(outstanding_interest).unwrap();
-                self.stable_loan_interest_rate = previous_debt.checked_add(
-                    outstanding_interest.checked_mul(rate).unwrap()
-                ).unwrap().checked_div(
-                    self.stable_loan_amount
-                ).unwrap();
             }
             else{
                 let should_paid = loan_amount.checked_add(interest).unwrap();
@@ -348,33 +1088,159 @@ mod lend_pool {
                 else{
                     repay_in_borrow = repay_amount.checked_sub(interest).unwrap();
                 }
-                
-                // The final repayment may be greater than the total amount borrowed.
-                // This is because each loan repayment is calculated separately.
-                if repay_in_borrow >= self.stable_loan_amount{
-                    self.stable_loan_amount = Decimal::ZERO;
-                    self.stable_loan_interest_rate = Decimal::ZERO;
-                }
-                else{
-                    self.stable_loan_amount = self.stable_loan_amount.checked_sub(repay_in_borrow).unwrap();
-                    self.stable_loan_interest_rate = previous_debt.checked_sub(
-                        repay_in_borrow.checked_mul(rate).unwrap()
-                    ).unwrap().checked_div(
-                        self.stable_loan_amount
-                    ).unwrap();
-                }
             }
-            
+            self.reduce_stable_loan(repay_amount, rate);
+
             self.vault.put(repay_bucket.take(repay_amount));
+            self.fill_withdrawal_queue();
 
             self.update_interest_rate();
 
-            (repay_bucket, repay_amount, repay_in_borrow, interest, current_epoch_at)
+            (repay_bucket, repay_amount, repay_in_borrow, interest, current_point)
+
+        }
+
+        /// Converts a stable loan (with its accrued interest) into variable loan shares.
+        /// Returns the variable loan shares and the capitalized interest.
+        pub fn swap_to_variable(&mut self, loan_amount: Decimal, rate: Decimal, last_epoch_at: u64) -> (Decimal, Decimal){
+            self.update_index();
+
+            let interest = self.calc_stable_interest(loan_amount, rate, last_epoch_at);
+            self.reduce_stable_loan(loan_amount.checked_add(interest).unwrap(), rate);
+            let variable_share = ceil(
+                loan_amount.checked_add(interest).unwrap().checked_div(self.loan_index).unwrap(),
+                self.deposit_share_res_mgr.resource_type().divisibility().unwrap()
+            );
+            self.variable_loan_share_quantity = self.variable_loan_share_quantity.checked_add(variable_share).unwrap();
+
+            self.update_interest_rate();
+
+            (variable_share, interest)
+        }
+
+        /// Converts variable loan shares into a stable loan at the given stable rate.
+        /// Returns the amount of the stable loan.
+        pub fn swap_to_stable(&mut self, normalized_amount: Decimal, stable_rate: Decimal) -> Decimal{
+            self.assert_not_paused(Operation::Borrow);
+            self.update_index();
+
+            let loan_amount = ceil_by_resource(self.underlying_token, normalized_amount.checked_mul(self.loan_index).unwrap());
+            self.variable_loan_share_quantity = self.variable_loan_share_quantity.checked_sub(normalized_amount).unwrap();
+            self.stable_loan_interest_rate = get_weight_rate(self.stable_loan_amount, self.stable_loan_interest_rate, loan_amount, stable_rate);
+            self.stable_loan_amount = self.stable_loan_amount.checked_add(loan_amount).unwrap();
+
+            self.update_interest_rate();
+
+            loan_amount
+        }
+
+        /// Resets a stable loan (with its accrued interest) to the new stable rate.
+        /// Returns the amount of the stable loan and the capitalized interest.
+        pub fn rebalance_stable(&mut self, loan_amount: Decimal, rate: Decimal, last_epoch_at: u64, new_rate: Decimal) -> (Decimal, Decimal){
+            self.update_index();
+
+            let interest = self.calc_stable_interest(loan_amount, rate, last_epoch_at);
+            self.reduce_stable_loan(loan_amount.checked_add(interest).unwrap(), rate);
+            let new_loan_amount = loan_amount.checked_add(interest).unwrap();
+            self.stable_loan_interest_rate = get_weight_rate(self.stable_loan_amount, self.stable_loan_interest_rate, new_loan_amount, new_rate);
+            self.stable_loan_amount = self.stable_loan_amount.checked_add(new_loan_amount).unwrap();
+
+            self.update_interest_rate();
+
+            (new_loan_amount, interest)
+        }
+
+        /// Writes off the variable loan shares that can no longer be repaid.
+        /// Returns (bad debt, loss covered by insurance, loss socialized to depositors).
+        pub fn write_off_variable(&mut self, normalized_amount: Decimal) -> (Decimal, Decimal, Decimal){
+            self.update_index();
+
+            let debt_amount = ceil_by_resource(self.underlying_token, normalized_amount.checked_mul(self.loan_index).unwrap());
+            self.variable_loan_share_quantity = self.variable_loan_share_quantity.checked_sub(normalized_amount).unwrap();
+            let (insurance_loss, socialized_loss) = self.absorb_loss(debt_amount);
+
+            self.update_interest_rate();
+
+            (debt_amount, insurance_loss, socialized_loss)
+        }
+
+        /// Writes off the stable loan (with its accrued interest) that can no longer be repaid.
+        /// Returns (bad debt, loss covered by insurance, loss socialized to depositors).
+        pub fn write_off_stable(&mut self, loan_amount: Decimal, rate: Decimal, last_epoch_at: u64) -> (Decimal, Decimal, Decimal){
+            self.update_index();
+
+            let interest = self.calc_stable_interest(loan_amount, rate, last_epoch_at);
+            self.reduce_stable_loan(loan_amount.checked_add(interest).unwrap(), rate);
+            let debt_amount = loan_amount.checked_add(interest).unwrap();
+            let (insurance_loss, socialized_loss) = self.absorb_loss(debt_amount);
+
+            self.update_interest_rate();
+
+            (debt_amount, insurance_loss, socialized_loss)
+        }
+
+        ///
+        /// The loss is covered by the insurance balance first. The rest is shared by the floating and the fixed-term deposits
+        /// pro rata to their funds: it reduces the deposit index and the term deposit index.
+        fn absorb_loss(&mut self, loss: Decimal) -> (Decimal, Decimal){
+            let insurance_loss = if loss > self.insurance_balance { self.insurance_balance } else { loss };
+            self.insurance_balance = self.insurance_balance.checked_sub(insurance_loss).unwrap();
+
+            let socialized_loss = loss.checked_sub(insurance_loss).unwrap();
+            let deposit_share_quantity = self.get_deposit_share_quantity();
+            let floating_funds = deposit_share_quantity.checked_mul(self.deposit_index).unwrap();
+            let term_funds = self.get_term_deposit_funds();
+            let funds = floating_funds.checked_add(term_funds).unwrap();
+            if socialized_loss.is_positive() && funds.is_positive() {
+                let term_loss = socialized_loss.checked_mul(term_funds).unwrap().checked_div(funds).unwrap();
+                let floating_loss = socialized_loss.checked_sub(term_loss).unwrap();
+                if floating_funds.is_positive() {
+                    let delta_index = floating_loss.checked_div(deposit_share_quantity).unwrap();
+                    self.deposit_index = if delta_index >= self.deposit_index { Decimal::ZERO } else { self.deposit_index.checked_sub(delta_index).unwrap() };
+                }
+                if term_funds.is_positive() {
+                    let delta_index = term_loss.checked_mul(self.term_deposit_index).unwrap().checked_div(term_funds).unwrap();
+                    self.term_deposit_index = if delta_index >= self.term_deposit_index { Decimal::ZERO } else { self.term_deposit_index.checked_sub(delta_index).unwrap() };
+                }
+            }
+            info!("absorb_loss({}): loss:{}, insurance:{}, socialized:{}, deposit_index:{}", Runtime::bech32_encode_address(self.underlying_token), loss, insurance_loss, socialized_loss, self.deposit_index);
+            (insurance_loss, socialized_loss)
+        }
+
+        fn calc_stable_interest(&self, loan_amount: Decimal, rate: Decimal, last_epoch_at: u64) -> Decimal{
+            if self.get_accrual_point() <= last_epoch_at {
+                Decimal::ZERO
+            } else { 
+                ceil_by_resource(
+                    self.underlying_token, 
+                    loan_amount.checked_mul(
+                        self.calc_compound_rate_since(rate, last_epoch_at)
+                    ).unwrap().checked_sub(loan_amount).unwrap()
+                )
+            }
+        }
 
+        /// Removes a stable loan (with the interest accrued by the pool) from the pool and re-weights the average stable rate.
+        fn reduce_stable_loan(&mut self, amount: Decimal, rate: Decimal){
+            // The final repayment may be greater than the total amount borrowed.
+            // This is because each loan repayment is calculated separately.
+            if amount >= self.stable_loan_amount{
+                self.stable_loan_amount = Decimal::ZERO;
+                self.stable_loan_interest_rate = Decimal::ZERO;
+            }
+            else{
+                let previous_debt = self.stable_loan_amount.checked_mul(self.stable_loan_interest_rate).unwrap();
+                self.stable_loan_amount = self.stable_loan_amount.checked_sub(amount).unwrap();
+                self.stable_loan_interest_rate = previous_debt.checked_sub(
+                    amount.checked_mul(rate).unwrap()
+                ).unwrap().checked_div(
+                    self.stable_loan_amount
+                ).unwrap();
+            }
         }
 
         pub fn borrow_fixed_term(&mut self, amount: Decimal) -> FungibleBucket {
-            assert!(self.vault.amount() >= amount, "Insufficient vault amount!");
+            assert!(self.get_fillable_liquidity() >= amount, "Insufficient vault amount!");
             self.vault.take_advanced(amount, TO_ZERO)
         }
 
@@ -382,16 +1248,23 @@ mod lend_pool {
             let total = ceil_by_resource(self.underlying_token.clone(), amount.checked_add(fee).unwrap());
             assert!(repay_bucket.amount() >= total, "Insufficient repay amount!");
             self.vault.put(repay_bucket.take(total));
+            self.fill_withdrawal_queue();
             if fee > Decimal::ZERO {
                 self.update_index();
                 
-                let (supply_index, _) = self.get_current_index();
-                let supply: Decimal = self.get_deposit_share_quantity().checked_mul(supply_index).unwrap();
+                let supply: Decimal = self.get_deposit_share_quantity().checked_mul(self.deposit_index).unwrap();
                 
-                let insurance = fee.checked_mul(self.insurance_ratio).unwrap();
+                let mut insurance = fee.checked_mul(self.flashloan_insurance_ratio).unwrap();
+                let treasury = fee.checked_mul(self.flashloan_treasury_ratio).unwrap();
+                let depositor_fee = fee.checked_sub(insurance).unwrap().checked_sub(treasury).unwrap();
+                if supply.is_zero() {
+                    // no floating deposit to share the fee, it goes into the insurance.
+                    insurance = insurance.checked_add(depositor_fee).unwrap();
+                } else {
+                    self.deposit_index = self.deposit_index.checked_add(depositor_fee.checked_div(supply).unwrap()).unwrap();
+                }
                 self.insurance_balance = self.insurance_balance.checked_add(insurance).unwrap();
-                let cumulate_to_supply_index = fee.checked_sub(insurance).unwrap().checked_div(supply).unwrap();
-                self.deposit_index = supply_index.checked_add(cumulate_to_supply_index).unwrap();
+                self.treasury_balance = self.treasury_balance.checked_add(treasury).unwrap();
 
                 self.update_interest_rate();
             }
@@ -402,6 +1275,7 @@ mod lend_pool {
             let nft_id = claim_nft.non_fungible_global_id();
             let data = claim_nft.non_fungible::<UnstakeData>().data();
             let epoch_at = data.claim_epoch.number();
+            let principal = data.claim_amount.checked_sub(interest).unwrap();
             
             match self.bond_epochs.binary_search(&epoch_at) {
                 Ok(_) => (),
@@ -414,73 +1288,43 @@ mod lend_pool {
                 self.bonds.insert(epoch_at, FixedEpochBond{
                     epoch_at,
                     interest,
+                    principal,
                     global_id_list
                 });
             }
             else{
                 let mut entry = self.bonds.get_mut(&epoch_at).unwrap();
                 entry.interest = entry.interest.checked_add(interest).unwrap();
+                entry.principal = entry.principal.checked_add(principal).unwrap();
                 entry.global_id_list.push(nft_id);
             }
 
             self.claim_nfts.put(claim_nft);
-            self.bond_amount = self.bond_amount.checked_add(
-                data.claim_amount.checked_sub(interest).unwrap()
-            ).unwrap();
+            self.bond_amount = self.bond_amount.checked_add(principal).unwrap();
+            Runtime::emit_event(AddBondEvent{
+                underlying_token: self.underlying_token,
+                epoch_at,
+                claim_amount: data.claim_amount,
+                interest,
+                bond_amount: self.bond_amount
+            });
         }
 
         ///
-        /// Retrieves the total amount of mature bonds and the total interest of the mature bonds.
-        fn get_mature_bonds(&self) -> (Decimal, Decimal) {
-            let current_epoch = Runtime::current_epoch().number();
-            let mut sum = Decimal::ZERO;
-            let mut interest = Decimal::ZERO;
-            
-            for epoch in self.bond_epochs.iter() {
-                if *epoch > current_epoch {
-                    break;
-                }
-                let epoch_entry = self.bonds.get(epoch);
-                if epoch_entry.is_some() {
-                    let entry = epoch_entry.unwrap();
-                    let nft_ids = entry.global_id_list.range(0, entry.global_id_list.len());
-                    sum = sum.checked_add(Self::sum_claim_amount(nft_ids)).unwrap();
-                    interest = interest.checked_add(entry.interest).unwrap();
-                }
-            }
-            (sum, interest)
-        }
-
-        fn sum_claim_amount(nft_ids: Vec<NonFungibleGlobalId>) -> Decimal{
-            let mut sum = Decimal::ZERO;
-            for nft_id in nft_ids {
-                let data = NonFungibleResourceManager::from(nft_id.resource_address()).get_non_fungible_data::<UnstakeData>(&nft_id.local_id());
-                sum = sum.checked_add(data.claim_amount).unwrap();
-            }
-            sum
-        }
-
+        /// Returns the indexes accrued by the interest rates since the last update.
+        /// The interest of the matured bonds is added to the deposit index only when their claim NFTs are claimed.
         pub fn get_current_index(&self) -> (Decimal, Decimal){
-            let current_epoch = Runtime::current_epoch().number();
-            let delta_epoch = current_epoch - self.last_update;
+            let current_point = self.get_accrual_point();
+            let delta_epoch = current_point - self.last_update;
             if delta_epoch <= 0u64{
                 return (self.deposit_index, self.loan_index);
             }
             
-            let epoch_of_year = Decimal::from(EPOCH_OF_YEAR);
+            let epoch_of_year = self.get_periods_of_year();
             // let delta_supply_interest_rate = calc_linear_rate(self.deposit_interest_rate, epoch_of_year, delta_epoch);
             // info!("epoch:{}-{}, delta_epoch:{}, supply:{}==>{}, borrow:{}==>{}", current_epoch, self.last_update, delta_epoch, self.deposit_interest_rate,delta_supply_interest_rate, self.variable_loan_interest_rate, delta_borrow_interest_rate);
-            let mut index_of_deposit = calc_linear_interest(self.deposit_index, self.deposit_interest_rate, epoch_of_year, delta_epoch);
-            let (_, mature_interest) = self.get_mature_bonds();
-            if mature_interest > Decimal::ZERO {
-                let deposit_funds = self.get_deposit_share_quantity().checked_mul(index_of_deposit).unwrap();
-                let delta_index = mature_interest.checked_mul(
-                    Decimal::ONE - self.insurance_ratio
-                ).unwrap().checked_div(deposit_funds).unwrap();
-                index_of_deposit = index_of_deposit.checked_add(delta_index).unwrap();
-            }
             (
-                index_of_deposit,
+                calc_linear_interest(self.deposit_index, self.deposit_interest_rate, epoch_of_year, delta_epoch),
                 calc_compound_interest(self.loan_index, self.variable_loan_interest_rate, epoch_of_year, delta_epoch)
             )
         }
@@ -495,16 +1339,30 @@ mod lend_pool {
             self.calc_interest_rate(supply, variable_borrow, stable_borrow)
         }
 
+        /// Returns the total supply and the total outstanding loans (variable, stable and fixed-term) of the pool.
+        pub fn get_total_supply_borrow(&self) -> (Decimal, Decimal){
+            let (supply_index, variable_borrow_index) = self.get_current_index();
+            let supply: Decimal = self.get_deposit_share_quantity().checked_mul(supply_index).unwrap();
+            let variable_borrow = self.get_variable_share_quantity().checked_mul(variable_borrow_index).unwrap();
+            let stable_borrow = self.get_stable_loan_value();
+            (
+                supply.checked_add(self.get_term_deposit_funds()).unwrap(),
+                variable_borrow.checked_add(stable_borrow).unwrap().checked_add(self.bond_amount).unwrap()
+            )
+        }
+
         fn calc_interest_rate(&self, supply: Decimal, variable_borrow: Decimal, stable_borrow: Decimal) -> (Decimal, Decimal, Decimal){
 
             
-            let (mature_bond, _) = self.get_mature_bonds();
-            let bond = self.bond_amount.checked_sub(mature_bond).unwrap();
+            // the matured bonds stay illiquid until their claim NFTs are claimed.
+            let bond = self.bond_amount;
             let total_debt = variable_borrow.checked_add(stable_borrow).unwrap();
-            let borrow_ratio = if supply == Decimal::ZERO { Decimal::ZERO } else { total_debt.checked_div(supply).unwrap() };
+            // the funds of the floating and the fixed-term deposits
+            let funds = supply.checked_add(self.get_term_deposit_funds()).unwrap();
+            let borrow_ratio = if funds == Decimal::ZERO { Decimal::ZERO } else { total_debt.checked_div(funds).unwrap() };
             let stable_ratio = if total_debt == Decimal::ZERO {Decimal::ZERO } else { stable_borrow.checked_div(total_debt).unwrap() };
             let bond_ratio = if total_debt == Decimal::ZERO { Decimal::ZERO } else { bond.checked_div(total_debt).unwrap() };
-            info!("calc_interest_rate.0, var:{}, stable:{}, bond:{},{}, supply:{}", variable_borrow, stable_borrow, self.bond_amount, bond, supply);
+            info!("calc_interest_rate.0, var:{}, stable:{}, bond:{}, supply:{}", variable_borrow, stable_borrow, bond, supply);
             
             info!("calc_interest_rate.1, borrow_ratio:{}, stable_ratio:{}, bond_ratio:{}", borrow_ratio, stable_ratio, bond_ratio);
             let def_interest_model: Global<DefInterestModel> = Global::<DefInterestModel>::from(INTEREST_COMPONENT);
@@ -518,7 +1376,9 @@ mod lend_pool {
             
             //TODO: supply_rate = overall_borrow_rate * (1-insurance_ratio) * borrow_ratio ?
             let interest = total_debt.checked_mul(overall_borrow_rate).unwrap().checked_mul(Decimal::ONE.checked_sub(self.insurance_ratio).unwrap()).unwrap();
-            let supply_rate = if supply == Decimal::ZERO { Decimal::ZERO} else {interest.checked_div(supply).unwrap()};
+            // the accruing fixed-term deposits take their fixed interest first, the floating deposits share the rest.
+            let floating_interest = interest.checked_sub(self.get_accruing_term_deposit().checked_mul(self.term_deposit_interest_rate).unwrap()).unwrap();
+            let supply_rate = if supply == Decimal::ZERO || !floating_interest.is_positive() { Decimal::ZERO} else {floating_interest.checked_div(supply).unwrap()};
             info!("calc_interest_rate.3, interest:{}, overall_borrow_rate:{}, supply_rate:{} ", interest, overall_borrow_rate, supply_rate);
         
             (variable_rate, stable_rate, supply_rate)
@@ -526,40 +1386,131 @@ mod lend_pool {
 
         fn update_index(&mut self) {
             let current_epoch = Runtime::current_epoch().number();
-            let delta_epoch = current_epoch - self.last_update;
+            let current_point = self.get_accrual_point();
+            let delta_epoch = current_point - self.last_update;
             if delta_epoch > 0u64 {
                 // Liquidate matured bonds (NFTs) and distribute the accrued returns to all depositors (deposit share holders).
-                self.claim_matured_bonds();
+                self.claim_matured_bonds(BOND_CLAIM_LIMIT);
 
                 let (current_supply_index, current_borrow_index) = self.get_current_index();
                 
-                let epoch_of_year = Decimal::from(EPOCH_OF_YEAR);
+                let epoch_of_year = self.get_periods_of_year();
                 // variable loan share quantity
                 let variable_borrow: Decimal = self.variable_loan_share_quantity;
                 // variable loan interest = variable loan share quantity * (current index value - [last_update] index value)
                 let recent_variable_interest = variable_borrow.checked_mul(current_borrow_index.checked_sub(self.loan_index).unwrap()).unwrap();
                 // stable loan interest
                 let recent_stable_interest = calc_compound_interest(self.stable_loan_amount, self.stable_loan_interest_rate, epoch_of_year, delta_epoch).checked_sub(self.stable_loan_amount).unwrap();
+                // the stable loans accrue the interest, so that their value is counted from the current point.
+                self.stable_loan_amount = self.stable_loan_amount.checked_add(recent_stable_interest).unwrap();
+                self.stable_loan_last_update = current_point;
                 // deposit share quantity
                 let normalized_supply: Decimal = self.get_deposit_share_quantity();
                 // deposite interest
                 let recent_supply_interest = normalized_supply.checked_mul(current_supply_index.checked_sub(self.deposit_index).unwrap()).unwrap();
+                // fixed-term deposit interest, up to the maturity of each deposit
+                let recent_term_interest = self.accrue_term_deposits(current_point);
                 
                 // the interest rate spread goes into the insurance pool
-                // insurance_balance += variable_interest + stable_interest - recent_supply_interest
+                // insurance_balance += variable_interest + stable_interest - recent_supply_interest - recent_term_interest
+                let mut current_supply_index = current_supply_index;
                 self.insurance_balance = self.insurance_balance.checked_add(
                     recent_variable_interest.checked_add(recent_stable_interest).unwrap()
                     .checked_sub(recent_supply_interest).unwrap()
+                    .checked_sub(recent_term_interest).unwrap()
                 ).unwrap();
+                if self.insurance_balance.is_negative() {
+                    // the fixed interest exceeds the spread and the insurance, the floating deposits bear the shortfall.
+                    let shortfall = self.insurance_balance.checked_neg().unwrap();
+                    if normalized_supply.is_positive() {
+                        let delta_index = shortfall.checked_div(normalized_supply).unwrap();
+                        current_supply_index = if delta_index >= current_supply_index { Decimal::ZERO } else { current_supply_index.checked_sub(delta_index).unwrap() };
+                    }
+                    self.insurance_balance = Decimal::ZERO;
+                }
+                assert!(self.insurance_balance >= Decimal::ZERO, "The insurance balance is negative!");
     
                 info!("update_index({}), before loan_index:{}, current:{}, before supply_index:{}, current:{}, stable:{}, stable_avg_rate:{}", Runtime::bech32_encode_address(self.underlying_token), self.loan_index, current_borrow_index, self.deposit_index, current_supply_index, self.stable_loan_amount, self.stable_loan_interest_rate);
                 self.deposit_index = current_supply_index;
                 self.loan_index = current_borrow_index;
-                self.last_update = current_epoch;
+                self.last_update = current_point;
+                Runtime::emit_event(UpdateIndexEvent{
+                    underlying_token: self.underlying_token,
+                    epoch_at: current_epoch,
+                    deposit_index: self.deposit_index,
+                    loan_index: self.loan_index,
+                    deposit_interest_rate: self.deposit_interest_rate,
+                    variable_loan_interest_rate: self.variable_loan_interest_rate,
+                    insurance_balance: self.insurance_balance
+                });
+                if current_epoch >= self.last_checkpoint_epoch.checked_add(INDEX_CHECKPOINT_EPOCHS).unwrap() {
+                    self.record_index_checkpoint(current_epoch);
+                }
     
             }
         }
 
+        fn record_index_checkpoint(&mut self, epoch_at: u64){
+            let (supply, borrow) = self.get_total_supply_borrow();
+            let checkpoint = IndexCheckpoint{
+                epoch_at,
+                timestamp: Self::current_timestamp(),
+                deposit_index: self.deposit_index,
+                loan_index: self.loan_index,
+                utilization: if supply == Decimal::ZERO { Decimal::ZERO } else { borrow.checked_div(supply).unwrap() },
+                deposit_interest_rate: self.deposit_interest_rate,
+                variable_loan_interest_rate: self.variable_loan_interest_rate,
+                stable_loan_interest_rate: self.stable_loan_interest_rate
+            };
+            if self.index_history.len() < INDEX_HISTORY_SIZE {
+                self.index_history.push(checkpoint);
+            } else {
+                self.index_history.update(self.index_checkpoint_count % INDEX_HISTORY_SIZE, checkpoint);
+            }
+            self.index_checkpoint_count += 1;
+            self.last_checkpoint_epoch = epoch_at;
+        }
+
+        /// Returns the kept checkpoints from the oldest to the latest.
+        fn get_checkpoints(&self) -> Vec<IndexCheckpoint>{
+            let len = self.index_history.len();
+            if len < INDEX_HISTORY_SIZE {
+                return self.index_history.range(0, len);
+            }
+            let oldest = self.index_checkpoint_count % INDEX_HISTORY_SIZE;
+            let mut checkpoints = self.index_history.range(oldest, len);
+            checkpoints.extend(self.index_history.range(0, oldest));
+            checkpoints
+        }
+
+        /// Returns the checkpoints recorded between the epochs `from` and `to` (inclusive).
+        pub fn get_index_history(&self, from: u64, to: u64) -> Vec<IndexCheckpoint>{
+            self.get_checkpoints().into_iter().filter(|checkpoint| checkpoint.epoch_at >= from && checkpoint.epoch_at <= to).collect()
+        }
+
+        ///
+        /// Returns the realized (supply APY, variable borrow APY) over the trailing window of 1, 7 or 30 days,
+        /// annualized from the growth of the indices since the checkpoint at the start of the window.
+        /// The oldest checkpoint is used if the history is shorter than the window.
+        pub fn get_trailing_apy(&self, days: u64) -> (Decimal, Decimal){
+            assert!(days == 1 || days == 7 || days == 30, "The trailing window must be 1, 7 or 30 days!");
+            let now = Self::current_timestamp();
+            let window_start = now.saturating_sub(days.checked_mul(86400u64).unwrap());
+            let checkpoints = self.get_checkpoints();
+            let base = checkpoints.iter().rev().find(|checkpoint| checkpoint.timestamp <= window_start).or(checkpoints.first());
+            match base {
+                Some(checkpoint) if now > checkpoint.timestamp => {
+                    let (deposit_index, loan_index) = self.get_current_index();
+                    let elapsed = now - checkpoint.timestamp;
+                    (
+                        annualize_index_growth(checkpoint.deposit_index, deposit_index, elapsed),
+                        annualize_index_growth(checkpoint.loan_index, loan_index, elapsed)
+                    )
+                },
+                _ => (Decimal::ZERO, Decimal::ZERO)
+            }
+        }
+
         fn update_interest_rate(&mut self){
             let (supply_index, variable_borrow_index) = self.get_current_index();
             // This supply could be equal to zero.
@@ -572,54 +1523,101 @@ mod lend_pool {
             self.variable_loan_interest_rate = variable_rate;
         }
 
-        /// Claims matured bonds (NFTs) and distributes the accrued returns to all depositors.
-        fn claim_matured_bonds(&mut self) {
+        ///
+        /// Claims at most `max_nfts` matured bonds (NFTs) from the cursor. The principal and the interest of each claimed batch
+        /// are settled right away: the interest is distributed to the depositors of the moment by the deposit index.
+        /// Returns the number of the claimed NFTs.
+        fn claim_matured_bonds(&mut self, max_nfts: u64) -> u64 {
             let current_epoch = Runtime::current_epoch().number();
-            let mut interest = Decimal::ZERO;
+            let mut claimed = 0u64;
 
-            while let Some(epoch) = self.bond_epochs.first() {
-                if *epoch > current_epoch {
+            while let Some(epoch) = self.bond_epochs.first().cloned() {
+                if epoch > current_epoch || claimed >= max_nfts {
                     break;
                 }
-                if let Some(mut entry) = self.bonds.get_mut(epoch) {
-                    interest = interest.checked_add(entry.interest).unwrap();
-                    let nft_ids = entry.global_id_list.range(0, entry.global_id_list.len());
+                let cursor = self.bond_cursor;
+                let batch = self.bonds.get_mut(&epoch).map(|mut entry| {
+                    let len = entry.global_id_list.len();
+                    let end = len.min(cursor.saturating_add(max_nfts - claimed));
+                    let nft_ids = entry.global_id_list.range(cursor, end);
+                    let mut claim_amount = Decimal::ZERO;
                     if !nft_ids.is_empty() {
                         let nft_buckets = self.claim_nfts.take_nft_batch(nft_ids);
                         for bucket in nft_buckets {
                             let mut validator: Global<Validator> = get_validator(bucket.resource_address());
                             let claim_bucket = validator.claim_xrd(bucket);
-                            self.bond_amount = self.bond_amount.checked_sub(
-                                claim_bucket.amount().checked_sub(entry.interest).unwrap()
-                            ).unwrap();
+                            claim_amount = claim_amount.checked_add(claim_bucket.amount()).unwrap();
                             self.vault.put(claim_bucket);
                         }
                     }
-                    entry.clear();
+
+                    // the last batch of the epoch takes the rest, the others take their share of the claim amount.
+                    let (principal, interest) = if end == len {
+                        (entry.principal, entry.interest)
+                    } else {
+                        let ratio = claim_amount.checked_div(entry.principal.checked_add(entry.interest).unwrap()).unwrap();
+                        let interest = entry.interest.checked_mul(ratio).unwrap().min(entry.interest);
+                        (claim_amount.checked_sub(interest).unwrap().min(entry.principal), interest)
+                    };
+                    entry.principal = entry.principal.checked_sub(principal).unwrap();
+                    entry.interest = entry.interest.checked_sub(interest).unwrap();
+                    if end == len {
+                        entry.clear();
+                    }
+                    (end - cursor, principal, interest, end < len)
+                });
+
+                if let Some((nfts, principal, interest, unfinished)) = batch {
+                    claimed += nfts;
+                    self.bond_amount = self.bond_amount.checked_sub(principal).unwrap();
+                    self.distribute_bond_interest(interest);
+                    Runtime::emit_event(ClaimBondEvent{
+                        underlying_token: self.underlying_token,
+                        epoch_at: epoch,
+                        claim_nfts: nfts as usize,
+                        interest,
+                        bond_amount: self.bond_amount
+                    });
+                    if unfinished {
+                        // the rest of the epoch is left to the next call.
+                        self.bond_cursor = cursor + nfts;
+                        break;
+                    }
                 }
                 // self.bonds.remove(epoch);
                 self.bond_epochs.remove(0);
+                self.bond_cursor = 0;
             }
-            
-            if interest > Decimal::ZERO {
-                let insurance = interest.checked_mul(self.insurance_ratio).unwrap();
-                let deposit_funds = self.get_deposit_share_quantity().checked_mul(self.deposit_index).unwrap();
-                // It is impossible for `interest` to be positive when `deposit_funds` is zero.
-                let delta_index = interest.checked_sub(insurance).unwrap().checked_div(deposit_funds).unwrap();
 
-                self.insurance_balance = self.insurance_balance.checked_add(insurance).unwrap();
-                self.deposit_index = self.deposit_index.checked_add(delta_index).unwrap();
+            self.fill_withdrawal_queue();
+            claimed
+        }
+
+        /// Adds the interest of the claimed bonds to the deposit index, less the insurance.
+        /// The interest goes into the insurance as a whole when there is no floating deposit.
+        fn distribute_bond_interest(&mut self, interest: Decimal){
+            if !interest.is_positive() {
+                return;
             }
+            let deposit_funds = self.get_deposit_share_quantity().checked_mul(self.deposit_index).unwrap();
+            if deposit_funds.is_zero() {
+                self.insurance_balance = self.insurance_balance.checked_add(interest).unwrap();
+                return;
+            }
+            let insurance = interest.checked_mul(self.insurance_ratio).unwrap();
+            let delta_index = interest.checked_sub(insurance).unwrap().checked_div(deposit_funds).unwrap();
+            self.insurance_balance = self.insurance_balance.checked_add(insurance).unwrap();
+            self.deposit_index = self.deposit_index.checked_add(delta_index).unwrap();
         }
 
         fn get_stable_loan_value(&self) -> Decimal{
-            let delta_epoch = Runtime::current_epoch().number() - self.stable_loan_last_update;
-            if delta_epoch <= 0u64{
+            if self.get_accrual_point() <= self.stable_loan_last_update {
                 return self.stable_loan_amount;
             }
 
-            let epoch_of_year = Decimal::from(EPOCH_OF_YEAR);
-            calc_compound_interest(self.stable_loan_amount, self.stable_loan_interest_rate, epoch_of_year, delta_epoch)
+            self.stable_loan_amount.checked_mul(
+                self.calc_compound_rate_since(self.stable_loan_interest_rate, self.stable_loan_last_update)
+            ).unwrap()
         }
 
         pub fn get_redemption_value(&self, amount_of_pool_units: Decimal) -> Decimal{
@@ -637,8 +1635,7 @@ mod lend_pool {
 
         /// .
         pub fn get_stable_interest(&self, borrow_amount: Decimal, last_epoch: u64, stable_rate: Decimal) -> Decimal{
-            let delta_epoch = Runtime::current_epoch().number() - last_epoch;
-            calc_compound_interest(borrow_amount, stable_rate, Decimal::from(EPOCH_OF_YEAR), delta_epoch).checked_sub(borrow_amount).unwrap()
+            borrow_amount.checked_mul(self.calc_compound_rate_since(stable_rate, last_epoch)).unwrap().checked_sub(borrow_amount).unwrap()
         }
 
         pub fn get_variable_interest(&self, borrow_amount: Decimal) -> Decimal{
@@ -649,6 +1646,126 @@ mod lend_pool {
         fn get_variable_share_quantity(&self) -> Decimal{
             self.variable_loan_share_quantity
         }
+
+        /// The principal of the accruing and the matured fixed-term deposits.
+        fn get_term_deposit_funds(&self) -> Decimal{
+            self.term_deposit_amount.checked_add(self.term_deposit_matured).unwrap().checked_mul(self.term_deposit_index).unwrap()
+        }
+
+        /// The principal of the fixed-term deposits accruing the fixed interest.
+        fn get_accruing_term_deposit(&self) -> Decimal{
+            self.term_deposit_amount.checked_mul(self.term_deposit_index).unwrap()
+        }
     }   
 
-}
\ No newline at end of file
+}
+
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct AddLiquidityEvent{
+    pub underlying_token: ResourceAddress,
+    pub amount: Decimal,
+    pub dx_amount: Decimal,
+    pub deposit_index: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct RemoveLiquidityEvent{
+    pub underlying_token: ResourceAddress,
+    pub amount: Decimal,
+    pub dx_amount: Decimal,
+    pub deposit_index: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct WithdrawInsuranceEvent{
+    pub underlying_token: ResourceAddress,
+    pub amount: Decimal,
+    pub insurance_balance: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct AddBondEvent{
+    pub underlying_token: ResourceAddress,
+    pub epoch_at: u64,
+    pub claim_amount: Decimal,
+    pub interest: Decimal,
+    pub bond_amount: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ClaimBondEvent{
+    pub underlying_token: ResourceAddress,
+    pub epoch_at: u64,
+    pub claim_nfts: usize,
+    pub interest: Decimal,
+    pub bond_amount: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct UpdateIndexEvent{
+    pub underlying_token: ResourceAddress,
+    pub epoch_at: u64,
+    pub deposit_index: Decimal,
+    pub loan_index: Decimal,
+    pub deposit_interest_rate: Decimal,
+    pub variable_loan_interest_rate: Decimal,
+    pub insurance_balance: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct RequestWithdrawalEvent{
+    pub underlying_token: ResourceAddress,
+    pub request_id: NonFungibleLocalId,
+    // the redemption value locked at the request
+    pub amount: Decimal,
+    pub dx_amount: Decimal,
+    pub deposit_index: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct FillWithdrawalEvent{
+    pub underlying_token: ResourceAddress,
+    pub request_id: NonFungibleLocalId,
+    pub amount: Decimal,
+    pub unfilled: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ClaimWithdrawalEvent{
+    pub underlying_token: ResourceAddress,
+    pub request_id: NonFungibleLocalId,
+    pub amount: Decimal,
+    // the amount still waiting for the liquidity
+    pub remaining: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct CancelWithdrawalEvent{
+    pub underlying_token: ResourceAddress,
+    pub request_id: NonFungibleLocalId,
+    // the filled amount paid out
+    pub amount: Decimal,
+    // the dx tokens minted for the unfilled amount
+    pub dx_amount: Decimal,
+    pub deposit_index: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct DepositTermEvent{
+    pub underlying_token: ResourceAddress,
+    pub deposit_id: NonFungibleLocalId,
+    pub amount: Decimal,
+    pub fixed_rate: Decimal,
+    pub maturity_point: u64
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct RedeemTermDepositEvent{
+    pub underlying_token: ResourceAddress,
+    pub deposit_id: NonFungibleLocalId,
+    pub principal: Decimal,
+    pub interest: Decimal,
+    // the penalty of the early exit, zero at maturity
+    pub penalty: Decimal
+}
@@ -0,0 +1,199 @@
+use scrypto::prelude::*;
+use common::*;
+
+/// The source of the protocol revenue received by the treasury.
+#[derive(ScryptoSbor, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum RevenueSource {
+    // the insurance withdrawn from the lending pools
+    Insurance,
+    // the treasury share of the flash loan fee
+    FlashLoanFee,
+    // the performance fee of the staking earning
+    PerformanceFee
+}
+
+/// A spending of the treasury waiting for the approval of the admin.
+#[derive(ScryptoSbor, Clone)]
+pub struct Spending {
+    pub res_addr: ResourceAddress,
+    pub amount: Decimal,
+    // the account to receive the funds
+    pub recipient: ComponentAddress,
+    pub memo: String,
+    pub proposed_epoch: u64
+}
+
+#[blueprint]
+#[types(
+    ResourceAddress,
+    FungibleVault
+)]
+#[events(DepositRevenueEvent, ProposeSpendingEvent, ApproveSpendingEvent, RejectSpendingEvent)]
+mod treasury {
+
+    enable_method_auth! {
+        roles{
+            authority => updatable_by:[];
+            admin => updatable_by: [authority];
+            operator => updatable_by: [authority];
+            depositor => updatable_by: [authority];
+        },
+        methods{
+            approve_spending => restrict_to:[admin];
+            reject_spending => restrict_to:[admin];
+            propose_spending => restrict_to:[operator, admin];
+            deposit => restrict_to:[depositor];
+
+            get_balance => PUBLIC;
+            get_revenues => PUBLIC;
+            get_pending_spendings => PUBLIC;
+        }
+    }
+
+    /**
+     * Treasury keeps the protocol reserves: the insurance withdrawn from the lending pools and the protocol fees.
+     * The revenue is recorded per asset and source, and the funds can only be released by a spending approved by the admin.
+     */
+    struct Treasury{
+        // vault for each asset of the reserves
+        vaults: Vaults,
+        // asset ==> source ==> accumulated revenue
+        revenues: HashMap<ResourceAddress, HashMap<RevenueSource, Decimal>>,
+        // spending id ==> spending waiting for approval
+        pending_spendings: HashMap<u64, Spending>,
+        // spending id counter
+        spending_id_counter: u64
+    }
+
+    impl Treasury {
+
+        pub fn instantiate(
+            owner_role: OwnerRole,
+            authority_rule: AccessRule,
+            admin_rule: AccessRule,
+            op_rule: AccessRule,
+            depositor_rule: AccessRule
+        ) -> Global<Treasury> {
+            Self{
+                vaults: Vaults::new(|| TreasuryKeyValueStore::new_with_registered_type()),
+                revenues: HashMap::new(),
+                pending_spendings: HashMap::new(),
+                spending_id_counter: 0u64
+            }
+            .instantiate()
+            .prepare_to_globalize(owner_role)
+            .roles(roles!{
+                authority => authority_rule;
+                admin => admin_rule;
+                operator => op_rule;
+                depositor => depositor_rule;
+            })
+            .globalize()
+        }
+
+        pub fn deposit(&mut self, bucket: FungibleBucket, source: RevenueSource){
+            let res_addr = bucket.resource_address();
+            let amount = bucket.amount();
+            self.vaults.put(bucket);
+            let revenue = self.revenues.entry(res_addr).or_default().entry(source).or_insert(Decimal::ZERO);
+            *revenue = revenue.checked_add(amount).unwrap();
+            Runtime::emit_event(DepositRevenueEvent{
+                res_addr,
+                source,
+                amount,
+                balance: self.vaults.amount(&res_addr)
+            });
+        }
+
+        ///
+        /// Proposes to send `amount` of the asset to the recipient account, the funds are released when the admin approves it.
+        /// Returns the id of the spending.
+        pub fn propose_spending(&mut self, res_addr: ResourceAddress, amount: Decimal, recipient: ComponentAddress, memo: String) -> u64{
+            assert!(amount.is_positive(), "The amount of the spending must be positive!");
+            assert!(recipient.as_node_id().is_global_account(), "The recipient must be an account!");
+            let proposed_epoch = Runtime::current_epoch().number();
+            self.spending_id_counter += 1;
+            self.pending_spendings.insert(self.spending_id_counter, Spending{
+                res_addr,
+                amount,
+                recipient,
+                memo: memo.clone(),
+                proposed_epoch
+            });
+            Runtime::emit_event(ProposeSpendingEvent{
+                spending_id: self.spending_id_counter,
+                res_addr,
+                amount,
+                recipient,
+                memo
+            });
+            self.spending_id_counter
+        }
+
+        pub fn approve_spending(&mut self, spending_id: u64){
+            let spending = self.pending_spendings.remove(&spending_id).expect("The spending does not exist!");
+            assert!(spending.amount <= self.vaults.amount(&spending.res_addr), "The treasury balance is insufficient!");
+            let bucket = self.vaults.take(&spending.res_addr, spending.amount);
+            let mut recipient: Global<Account> = Global::from(spending.recipient);
+            recipient.try_deposit_or_abort(bucket.into(), None);
+            Runtime::emit_event(ApproveSpendingEvent{
+                spending_id,
+                res_addr: spending.res_addr,
+                amount: spending.amount,
+                recipient: spending.recipient,
+                balance: self.vaults.amount(&spending.res_addr)
+            });
+        }
+
+        pub fn reject_spending(&mut self, spending_id: u64){
+            self.pending_spendings.remove(&spending_id).expect("The spending does not exist!");
+            Runtime::emit_event(RejectSpendingEvent{
+                spending_id
+            });
+        }
+
+        pub fn get_balance(&self, res_addr: ResourceAddress) -> Decimal{
+            self.vaults.amount(&res_addr)
+        }
+
+        pub fn get_revenues(&self, res_addr: ResourceAddress) -> HashMap<RevenueSource, Decimal>{
+            self.revenues.get(&res_addr).cloned().unwrap_or_default()
+        }
+
+        pub fn get_pending_spendings(&self) -> HashMap<u64, Spending>{
+            self.pending_spendings.clone()
+        }
+    }
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct DepositRevenueEvent{
+    pub res_addr: ResourceAddress,
+    pub source: RevenueSource,
+    pub amount: Decimal,
+    pub balance: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ProposeSpendingEvent{
+    pub spending_id: u64,
+    pub res_addr: ResourceAddress,
+    pub amount: Decimal,
+    pub recipient: ComponentAddress,
+    pub memo: String
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct ApproveSpendingEvent{
+    pub spending_id: u64,
+    pub res_addr: ResourceAddress,
+    pub amount: Decimal,
+    pub recipient: ComponentAddress,
+    // the balance of the asset after the spending
+    pub balance: Decimal
+}
+
+#[derive(ScryptoSbor, ScryptoEvent)]
+pub struct RejectSpendingEvent{
+    pub spending_id: u64
+}
@@ -0,0 +1,50 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_accrual_mode_migration() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let usdt_pool = interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(1));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(XRD, dec!(2500)).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "1")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(2000), usdt, dec!(500), bundle).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "1")]);
+    interface.borrow_stable(xrd_pool.dx_token, dec!(500), usdt, dec!(100), bundle).expect_commit_success();
+    assert_eq!(interface.get_accrual_mode(&usdt_pool), lending::AccrualMode::Epoch);
+
+    // the indexes accrue by epochs.
+    let (deposit_index_0, loan_index_0) = interface.get_current_index(&usdt_pool);
+    interface.advance_epochs(1000);
+    let (deposit_index_1, loan_index_1) = interface.get_current_index(&usdt_pool);
+    assert!(deposit_index_1 > deposit_index_0);
+    assert!(loan_index_1 > loan_index_0);
+
+    // the migration settles the indexes accrued by epochs, the stable loans keep their value.
+    let (_, total_borrow_1) = interface.get_total_supply_borrow(&usdt_pool);
+    assert!(total_borrow_1 > dec!(600) && total_borrow_1 < dec!(650));
+    interface.set_accrual_mode(usdt, ManifestAccrualMode::Timestamp).expect_commit_success();
+    assert_eq!(interface.get_accrual_mode(&usdt_pool), lending::AccrualMode::Timestamp);
+    assert_eq!(interface.get_current_index(&usdt_pool), (deposit_index_1, loan_index_1));
+    assert_eq!(interface.get_total_supply_borrow(&usdt_pool).1, total_borrow_1);
+
+    // the epochs no longer accrue, the clock does.
+    interface.advance_epochs(1000);
+    assert_eq!(interface.get_current_index(&usdt_pool), (deposit_index_1, loan_index_1));
+    interface.advance_seconds(86400);
+    let (deposit_index_2, loan_index_2) = interface.get_current_index(&usdt_pool);
+    assert!(deposit_index_2 > deposit_index_1);
+    assert!(loan_index_2 > loan_index_1);
+    let (_, total_borrow_2) = interface.get_total_supply_borrow(&usdt_pool);
+    assert!(total_borrow_2 > total_borrow_1 && total_borrow_2 < dec!(650));
+
+    // the migration is one way, the other pools keep the epoch accrual.
+    interface.set_accrual_mode(usdt, ManifestAccrualMode::Epoch).expect_commit_failure();
+    interface.set_accrual_mode(usdt, ManifestAccrualMode::Timestamp).expect_commit_success();
+    assert_eq!(interface.get_accrual_mode(&usdt_pool), lending::AccrualMode::Timestamp);
+    assert_eq!(interface.get_accrual_mode(&xrd_pool), lending::AccrualMode::Epoch);
+}
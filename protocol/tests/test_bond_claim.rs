@@ -0,0 +1,56 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_bond_claim_by_cursor() {
+    let mut interface = get_setup();
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+    let (_validator, lsu) = interface.new_staked_validator();
+
+    // the faster redemptions in the same epoch put all the claim NFTs into one bond.
+    let mut add_bond_events: Vec<lending::AddBondEvent> = Vec::new();
+    for _ in 0..7 {
+        let receipt = interface.redeem(lsu, dec!(100), true);
+        add_bond_events.extend(interface.parse_events::<lending::AddBondEvent>(&receipt));
+    }
+    assert_eq!(add_bond_events.len(), 7);
+    let claim_epoch = add_bond_events[0].epoch_at;
+    assert!(add_bond_events.iter().all(|event| event.epoch_at == claim_epoch));
+    let bond_interest = add_bond_events.iter().fold(Decimal::ZERO, |sum, event| sum + event.interest);
+
+    // nothing to claim before the claim epoch.
+    let receipt = interface.process_matured_bonds(&xrd_pool, 10);
+    assert_eq!(receipt.expect_commit_success().output::<u64>(1), 0u64);
+    assert!(interface.parse_events::<lending::ClaimBondEvent>(&receipt).is_empty());
+
+    let current_epoch = interface.current_epoch();
+    interface.advance_epochs(claim_epoch - current_epoch);
+
+    // the index update claims the first batch, the call claims one more NFT from the cursor.
+    let receipt = interface.process_matured_bonds(&xrd_pool, 1);
+    assert_eq!(receipt.expect_commit_success().output::<u64>(1), 1u64);
+    let mut claim_events = interface.parse_events::<lending::ClaimBondEvent>(&receipt);
+    assert_eq!(claim_events.len(), 2);
+    assert_eq!(claim_events[0].claim_nfts, 5);
+    assert_eq!(claim_events[1].claim_nfts, 1);
+    assert!(claim_events[1].bond_amount.is_positive());
+
+    // the last NFT settles the rest of the bond.
+    let receipt = interface.process_matured_bonds(&xrd_pool, 10);
+    assert_eq!(receipt.expect_commit_success().output::<u64>(1), 1u64);
+    let last_events = interface.parse_events::<lending::ClaimBondEvent>(&receipt);
+    assert_eq!(last_events.len(), 1);
+    assert_eq!(last_events[0].claim_nfts, 1);
+    assert_eq!(last_events[0].bond_amount, Decimal::ZERO);
+    claim_events.extend(last_events);
+
+    assert_eq!(claim_events.iter().map(|event| event.claim_nfts).sum::<usize>(), 7);
+    let claimed_interest = claim_events.iter().fold(Decimal::ZERO, |sum, event| sum + event.interest);
+    assert_eq!(claimed_interest, bond_interest);
+
+    let receipt = interface.process_matured_bonds(&xrd_pool, 10);
+    assert_eq!(receipt.expect_commit_success().output::<u64>(1), 0u64);
+    assert!(interface.parse_events::<lending::ClaimBondEvent>(&receipt).is_empty());
+}
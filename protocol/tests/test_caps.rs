@@ -0,0 +1,39 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_supply_and_borrow_caps() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+
+    // the caps are only set by the operator.
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .call_method(interface.components.cdp_component, "set_caps", manifest_args!(usdt, Some(dec!(1000)), Some(dec!(140))))
+        .build();
+    interface.execute(manifest).expect_commit_failure();
+    interface.set_caps(usdt, Some(dec!(1000)), Some(dec!(140))).expect_commit_success();
+
+    interface.supply(usdt, dec!(800)).expect_commit_success();
+    interface.supply(usdt, dec!(201)).expect_commit_failure();
+    interface.supply(usdt, dec!(200)).expect_commit_success();
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(100), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.extend_borrow(cdp_id, usdt, dec!(50), false, bundle).expect_commit_failure();
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.extend_borrow(cdp_id, usdt, dec!(40), false, bundle).expect_commit_success();
+
+    // lifting the borrow cap allows further borrowing.
+    interface.set_caps(usdt, Some(dec!(1000)), None).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.extend_borrow(cdp_id, usdt, dec!(5), false, bundle).expect_commit_success();
+    assert_eq!(interface.get_cdp(cdp_id).debts.get(&usdt).unwrap().total_borrow, dec!(145));
+}
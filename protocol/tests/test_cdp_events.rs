@@ -0,0 +1,51 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_cdp_lifecycle_events() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.supply(usdt, dec!(10000)).expect_commit_success();
+
+    let receipt = interface.supply(XRD, dec!(5000));
+    let event = interface.parse_event::<lending::AddLiquidityEvent>(&receipt);
+    assert_eq!(event.underlying_token, XRD);
+    assert_eq!(event.amount, dec!(5000));
+    assert_eq!(event.dx_amount, dec!(5000));
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    let receipt = interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(100), bundle);
+    let event = interface.parse_event::<BorrowEvent>(&receipt);
+    let cdp_id = event.cdp_id;
+    assert_eq!(cdp_id, 1u64);
+    assert_eq!(event.collateral_token, xrd_pool.dx_token);
+    assert_eq!(event.collateral_amount, dec!(5000));
+    assert_eq!(event.borrow_token, usdt);
+    assert_eq!(event.amount, dec!(100));
+    assert!(!event.is_stable);
+    assert!(event.normalized_amount.is_positive());
+    assert_eq!(event.stable_rate, Decimal::ZERO);
+
+    interface.advance_epochs(100);
+    let receipt = interface.repay(usdt, dec!(40), cdp_id);
+    let event = interface.parse_event::<RepayEvent>(&receipt);
+    assert_eq!(event.cdp_id, cdp_id);
+    assert_eq!(event.amount, dec!(40));
+    assert!(event.loan_index > Decimal::ONE);
+    assert!(!event.is_stable);
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    let receipt = interface.withdraw_collateral(cdp_id, xrd_pool.dx_token, dec!(1000), bundle);
+    let event = interface.parse_event::<WithdrawCollateralEvent>(&receipt);
+    assert_eq!(event.collateral_token, xrd_pool.dx_token);
+    assert_eq!(event.withdraw_token, XRD);
+    assert!(event.withdraw_amount <= dec!(1000));
+
+    let receipt = interface.close_cdp(cdp_id, &[(usdt, dec!(70))], false);
+    assert_eq!(interface.parse_event::<CloseCdpEvent>(&receipt).cdp_id, cdp_id);
+    assert_eq!(interface.parse_events::<RepayEvent>(&receipt).len(), 1);
+}
@@ -0,0 +1,39 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_cdp_status_by_cached_prices() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.supply(usdt, dec!(10000)).expect_commit_success();
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(125), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+
+    // 5000 XRD of collateral against 125 USDT (2500 XRD) of debt.
+    let status: CdpStatus = interface.get_cdp_status(cdp_id).expect_commit_success().output(1);
+    assert_eq!(status.collateral_value, dec!(5000));
+    assert_eq!(status.debt_value, dec!(2500));
+    assert_eq!(status.health_factor, dec!(3500) / dec!(2500));
+    assert_eq!(status.borrowable_value, dec!(500));
+    assert_eq!(status.debts.get(&usdt).copied(), Some(dec!(125)));
+    assert_eq!(status.liquidation_prices.get(&xrd_pool.dx_token).copied(), Some(dec!(2500) / dec!(3500)));
+
+    // a verified price signature refreshes the cached price: USDT falls to 16 XRD.
+    let bundle = interface.price_bundle(&[(usdt, "0.0625")]);
+    interface.extend_borrow(cdp_id, usdt, dec!(10), false, bundle).expect_commit_success();
+    let status: CdpStatus = interface.get_cdp_status(cdp_id).expect_commit_success().output(1);
+    assert_eq!(status.debt_value, dec!(2160));
+    assert_eq!(status.borrowable_value, dec!(840));
+    assert_eq!(status.health_factor, dec!(3500) / dec!(2160));
+
+    // the cached price expires with the epoch.
+    interface.advance_epochs(1);
+    interface.get_cdp_status(cdp_id).expect_commit_failure();
+}
@@ -0,0 +1,35 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_close_cdp_with_multiple_debts() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.supply(usdt, dec!(10000)).expect_commit_success();
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(100), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.extend_borrow(cdp_id, XRD, dec!(200), false, bundle).expect_commit_success();
+    interface.advance_epochs(1000);
+
+    // every debt must be settled.
+    interface.close_cdp(cdp_id, &[(usdt, dec!(110))], false).expect_commit_failure();
+
+    let usdt_balance = interface.test_account_balance(usdt);
+    let xrd_balance = interface.test_account_balance(XRD);
+    interface.close_cdp(cdp_id, &[(usdt, dec!(110)), (XRD, dec!(210))], false).expect_commit_success();
+
+    // the overpayment comes back along with the collateral, which has earned interest.
+    let usdt_paid = usdt_balance - interface.test_account_balance(usdt);
+    assert!(usdt_paid > dec!(100) && usdt_paid < dec!(110));
+    assert!(interface.test_account_balance(XRD) > xrd_balance + dec!(4790));
+    assert_eq!(interface.test_account_balance(xrd_pool.dx_token), Decimal::ZERO);
+    assert_eq!(interface.test_account_balance(interface.components.cdp_resource), Decimal::ZERO);
+}
@@ -0,0 +1,41 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_credit_delegation_allowance() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.supply(usdt, dec!(10000)).expect_commit_success();
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(50), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+
+    // only the pools of the protocol can be delegated.
+    let unknown = interface.create_token("UNK");
+    interface.delegate_credit(cdp_id, unknown, dec!(40)).expect_commit_failure();
+    interface.delegate_credit(cdp_id, usdt, dec!(40)).expect_commit_success();
+    let delegation_id = 1u64;
+    assert_eq!(interface.test_account_balance(interface.components.delegation_resource), Decimal::ONE);
+
+    // the delegate borrows within the allowance and the debt accrues on the CDP.
+    let usdt_balance = interface.test_account_balance(usdt);
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.extend_borrow_delegated(delegation_id, dec!(30), false, bundle).expect_commit_success();
+    assert_eq!(interface.test_account_balance(usdt), usdt_balance + dec!(30));
+    assert_eq!(interface.get_cdp(cdp_id).debts.get(&usdt).unwrap().total_borrow, dec!(80));
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.extend_borrow_delegated(delegation_id, dec!(20), false, bundle).expect_commit_failure();
+
+    // nothing can be borrowed once the owner revokes the remaining allowance.
+    interface.revoke_credit(cdp_id, delegation_id).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.extend_borrow_delegated(delegation_id, dec!(1), false, bundle).expect_commit_failure();
+    assert_eq!(interface.get_cdp(cdp_id).debts.get(&usdt).unwrap().total_borrow, dec!(80));
+}
@@ -0,0 +1,48 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_emode_for_correlated_assets() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let usdc = interface.create_token("USDC");
+    interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let usdc_pool = interface.new_pool(usdc, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.set_oracle_price(usdc, dec!(20));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(usdc, dec!(1000)).expect_commit_success();
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+
+    interface.set_asset_emode(usdt, Some(1u8)).expect_commit_failure();
+    interface.set_emode_category(1u8, "Stablecoins", dec!("0.93"), dec!("0.95"), dec!("0.02")).expect_commit_success();
+    interface.set_asset_emode(usdt, Some(1u8)).expect_commit_success();
+    interface.set_asset_emode(usdc, Some(1u8)).expect_commit_success();
+
+    // 100 USDC backs 80 USDT by the LTV of the asset.
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (usdc, "0.05")]);
+    interface.borrow_variable(usdc_pool.dx_token, dec!(100), usdt, dec!(75), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (usdc, "0.05")]);
+    interface.extend_borrow(cdp_id, usdt, dec!(10), false, bundle).expect_commit_failure();
+
+    // the category raises the LTV to 93%.
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (usdc, "0.05")]);
+    interface.set_cdp_emode(cdp_id, Some(2u8), bundle).expect_commit_failure();
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (usdc, "0.05")]);
+    interface.set_cdp_emode(cdp_id, Some(1u8), bundle).expect_commit_success();
+    assert_eq!(interface.get_cdp(cdp_id).emode_category, Some(1u8));
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (usdc, "0.05")]);
+    interface.extend_borrow(cdp_id, usdt, dec!(10), false, bundle).expect_commit_success();
+
+    // an asset out of the category can not be borrowed in efficiency mode.
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (usdc, "0.05")]);
+    interface.extend_borrow(cdp_id, XRD, dec!(10), false, bundle).expect_commit_failure();
+
+    // leaving the category would make the CDP unhealthy.
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (usdc, "0.05")]);
+    interface.set_cdp_emode(cdp_id, None, bundle).expect_commit_failure();
+    assert_eq!(interface.get_cdp(cdp_id).debts.get(&usdt).unwrap().total_borrow, dec!(85));
+}
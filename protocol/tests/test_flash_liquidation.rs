@@ -0,0 +1,42 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_flash_liquidation() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+
+    // 5000 XRD backs 125 USDT(2500 XRD).
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(125), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.flash_liquidation(usdt, dec!(50), cdp_id, xrd_pool.dx_token, Some(dec!(60)), bundle).expect_commit_failure();
+
+    // USDT rises to 40 XRD, the debt(5000 XRD) exceeds the liquidation threshold(3500 XRD).
+    interface.advance_epochs(1);
+    let xrd_balance = interface.test_account_balance(XRD);
+    let usdt_balance = interface.test_account_balance(usdt);
+
+    // the advance must be settled in the same transaction.
+    let bundle = interface.price_bundle(&[(usdt, "0.025")]);
+    interface.flash_liquidation(usdt, dec!(50), cdp_id, xrd_pool.dx_token, None, bundle).expect_commit_failure();
+    let bundle = interface.price_bundle(&[(usdt, "0.025")]);
+    interface.flash_liquidation(usdt, dec!(50), cdp_id, xrd_pool.dx_token, Some(dec!(60)), bundle).expect_commit_success();
+
+    // 50 USDT(2000 XRD) of the debt is repaid by the advance, the liquidator gets 2100 XRD with the bonus and pays the advance with its fee.
+    let cdp = interface.get_cdp(cdp_id);
+    let debt = cdp.debts.get(&usdt).unwrap();
+    assert_eq!(debt.total_repay, dec!(50));
+    assert!(cdp.get_collateral_amount(&xrd_pool.dx_token) < dec!(2901));
+    assert!(interface.test_account_balance(XRD) > xrd_balance + dec!(2099));
+    let usdt_paid = usdt_balance - interface.test_account_balance(usdt);
+    assert!(usdt_paid >= dec!(50) && usdt_paid <= dec!("50.2"));
+}
@@ -0,0 +1,26 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_flashloan_batch() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+
+    let loans = [(usdt, dec!(100)), (XRD, dec!(1000))];
+    interface.flashloan_batch(&[(usdt, dec!(100)), (usdt, dec!(100))], Some(&[(usdt, dec!("0.6"))])).expect_commit_failure();
+    interface.flashloan_batch(&loans, None).expect_commit_failure();
+    // every leg must be repaid with its fee.
+    interface.flashloan_batch(&loans, Some(&[(usdt, dec!("0.3"))])).expect_commit_failure();
+    interface.flashloan_batch(&loans, Some(&[(usdt, dec!("0.3")), (XRD, dec!("2.9"))])).expect_commit_failure();
+
+    let usdt_balance = interface.test_account_balance(usdt);
+    let xrd_balance = interface.test_account_balance(XRD);
+    interface.flashloan_batch(&loans, Some(&[(usdt, dec!("0.3")), (XRD, dec!(3))])).expect_commit_success();
+    assert_eq!(interface.test_account_balance(usdt), usdt_balance - dec!("0.3"));
+    assert_eq!(interface.test_account_balance(XRD), xrd_balance - dec!(3));
+}
@@ -0,0 +1,33 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_flashloan_fee_split_and_exempt() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let usdt_pool = interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+
+    interface.set_flashloan_fee_split(usdt, dec!("0.6"), dec!("0.5")).expect_commit_failure();
+    interface.set_flashloan_fee_split(usdt, dec!("0.2"), dec!("0.3")).expect_commit_success();
+
+    // the fee of 3 USDT: 0.6 to the insurance, 0.9 to the treasury and 1.5 to the depositors.
+    interface.flashloan(usdt, dec!(1000), dec!("2.9"), false).expect_commit_failure();
+    interface.flashloan(usdt, dec!(1000), dec!(3), false).expect_commit_success();
+    let (deposit_index, _) = interface.get_current_index(&usdt_pool);
+    assert_eq!(deposit_index, dec!("1.0015"));
+
+    interface.withdraw_treasury(usdt, dec!("0.9")).expect_commit_success();
+    interface.withdraw_treasury(usdt, dec!("0.01")).expect_commit_failure();
+    interface.withdraw_insurance(usdt, dec!("0.6")).expect_commit_success();
+    interface.withdraw_insurance(usdt, dec!("0.01")).expect_commit_failure();
+
+    // the holder of the fee exempt badge borrows without fee.
+    interface.flashloan(usdt, dec!(1000), Decimal::ZERO, true).expect_commit_failure();
+    interface.mint_fee_exempt_badge().expect_commit_success();
+    interface.flashloan(usdt, dec!(1000), Decimal::ZERO, false).expect_commit_failure();
+    interface.flashloan(usdt, dec!(1000), Decimal::ZERO, true).expect_commit_success();
+    let (deposit_index, _) = interface.get_current_index(&usdt_pool);
+    assert_eq!(deposit_index, dec!("1.0015"));
+}
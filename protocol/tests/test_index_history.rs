@@ -0,0 +1,41 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_index_checkpoints_and_trailing_apy() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let usdt_pool = interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(1));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(XRD, dec!(2000)).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "1")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(2000), usdt, dec!(500), bundle).expect_commit_success();
+
+    // a checkpoint is recorded at most once every 12 epochs, when the index is updated.
+    for _ in 0..3 {
+        interface.advance_epochs(12);
+        interface.advance_seconds(3600);
+        interface.supply(usdt, dec!(1)).expect_commit_success();
+    }
+    let current_epoch = interface.current_epoch();
+    let history = interface.get_index_history(&usdt_pool, current_epoch - 24, current_epoch);
+    assert_eq!(history.len(), 3);
+    for pair in history.windows(2) {
+        assert_eq!(pair[1].epoch_at, pair[0].epoch_at + 12);
+        assert!(pair[1].deposit_index > pair[0].deposit_index);
+        assert!(pair[1].loan_index > pair[0].loan_index);
+        assert!(pair[1].utilization.is_positive());
+    }
+    let recent = interface.get_index_history(&usdt_pool, history[1].epoch_at, u64::MAX);
+    assert_eq!(recent.len(), 2);
+    assert_eq!(recent[0].epoch_at, history[1].epoch_at);
+
+    // the history is shorter than a day, so the APY is annualized since the oldest checkpoint.
+    let (supply_apy, borrow_apy): (Decimal, Decimal) = interface.get_trailing_apy(&usdt_pool, 1u64).expect_commit_success().output(1);
+    assert!(supply_apy.is_positive());
+    assert!(borrow_apy > supply_apy);
+    interface.get_trailing_apy(&usdt_pool, 2u64).expect_commit_failure();
+}
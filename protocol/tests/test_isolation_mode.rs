@@ -0,0 +1,46 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_isolation_mode_debt_ceiling() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let eth = interface.create_token("ETH");
+    interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let eth_pool = interface.new_pool(eth, ManifestInterestModel::Default, dec!("0.7"), dec!("0.8"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.set_oracle_price(eth, dec!(50000));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+    interface.supply(eth, dec!(1)).expect_commit_success();
+
+    // ETH is isolated with a debt ceiling of 150 in units of the assets borrowable in isolation.
+    interface.set_isolation_mode(eth, true, dec!(150)).expect_commit_success();
+    interface.set_borrowable_in_isolation(usdt, true).expect_commit_success();
+
+    let bundle = interface.price_bundle(&[(eth, "0.00002")]);
+    interface.borrow_variable(eth_pool.dx_token, dec!("0.1"), XRD, dec!(100), bundle).expect_commit_failure();
+
+    // 0.1 ETH (3500 XRD by LTV) backs each CDP.
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.borrow_variable(eth_pool.dx_token, dec!("0.1"), usdt, dec!(100), bundle).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.borrow_variable(eth_pool.dx_token, dec!("0.1"), usdt, dec!(60), bundle).expect_commit_failure();
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.borrow_variable(eth_pool.dx_token, dec!("0.1"), usdt, dec!(50), bundle).expect_commit_success();
+    let (first_cdp, second_cdp) = (1u64, 2u64);
+
+    // an isolated collateral can not be mixed with other collaterals.
+    interface.addition_collateral(second_cdp, XRD, dec!(100), true).expect_commit_failure();
+
+    // repaying the principal with its interest only releases the principal from the ceiling.
+    interface.advance_epochs(1000);
+    interface.repay(usdt, dec!(110), first_cdp).expect_commit_success();
+    assert!(interface.get_cdp(first_cdp).debts.get(&usdt).unwrap().is_empty());
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.extend_borrow(second_cdp, usdt, dec!(100), false, bundle).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.extend_borrow(second_cdp, usdt, dec!("0.001"), false, bundle).expect_commit_failure();
+}
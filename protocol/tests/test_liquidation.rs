@@ -0,0 +1,69 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_liquidation_multi_asset_with_bad_debt() {
+    let mut interface = get_setup();
+    interface.fund_xrd();
+    let usdt = interface.create_token("USDT");
+    let eth = interface.create_token("ETH");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let usdt_pool = interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let eth_pool = interface.new_pool(eth, ManifestInterestModel::Default, dec!("0.7"), dec!("0.8"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.set_oracle_price(eth, dec!(50000));
+
+    interface.supply(usdt, dec!(10000)).expect_commit_success();
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+
+    // 5000 XRD backs 125 USDT(2500 XRD).
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(125), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+
+    // a new collateral type requires the proof of the CDP.
+    interface.addition_collateral(cdp_id, eth, dec!("0.05"), false).expect_commit_failure();
+    interface.addition_collateral(cdp_id, eth, dec!("0.05"), true).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.extend_borrow(cdp_id, usdt, dec!(75), false, bundle).expect_commit_success();
+
+    let cdp = interface.get_cdp(cdp_id);
+    assert_eq!(cdp.get_collateral_amount(&xrd_pool.dx_token), dec!(5000));
+    assert_eq!(cdp.get_collateral_amount(&eth_pool.dx_token), dec!("0.05"));
+
+    // the healthy CDP can not be liquidated.
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.liquidation(usdt, dec!(100), Decimal::ZERO, cdp_id, eth_pool.dx_token, bundle).expect_commit_failure();
+
+    interface.advance_epochs(1000);
+
+    // USDT rises to 50 XRD and ETH falls to 10000 XRD, the collaterals(5500 XRD) no longer cover the debt(10000 XRD).
+    let mut bad_debt_events: Vec<BadDebtEvent> = Vec::new();
+    for collateral_token in [eth_pool.dx_token, xrd_pool.dx_token] {
+        while interface.get_cdp(cdp_id).collaterals.contains_key(&collateral_token) {
+            let bundle = interface.price_bundle(&[(usdt, "0.02"), (eth, "0.0001")]);
+            let receipt = interface.liquidation(usdt, dec!(200), Decimal::ZERO, cdp_id, collateral_token, bundle);
+            receipt.expect_commit_success();
+            bad_debt_events.extend(interface.parse_events::<BadDebtEvent>(&receipt));
+        }
+    }
+
+    let cdp = interface.get_cdp(cdp_id);
+    assert!(cdp.collaterals.is_empty());
+    assert!(!cdp.has_debt());
+
+    assert_eq!(bad_debt_events.len(), 1);
+    let event = &bad_debt_events[0];
+    assert_eq!(event.cdp_id, cdp_id);
+    assert_eq!(event.borrow_token, usdt);
+    assert!(event.bad_debt.is_positive());
+    assert!(event.insurance_loss.is_positive());
+    assert!(event.socialized_loss.is_positive());
+    assert_eq!(event.insurance_loss + event.socialized_loss, event.bad_debt);
+
+    // the insurance does not cover the bad debt, the depositors take the rest.
+    let (deposit_index, _) = interface.get_current_index(&usdt_pool);
+    assert_eq!(deposit_index, event.deposit_index);
+    assert!(deposit_index < Decimal::ONE);
+}
@@ -0,0 +1,48 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_merge_and_split_cdp() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.supply(usdt, dec!(10000)).expect_commit_success();
+    interface.supply(XRD, dec!(9000)).expect_commit_success();
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(50), bundle).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(2000), usdt, dec!(20), bundle).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_stable(xrd_pool.dx_token, dec!(2000), usdt, dec!(20), bundle).expect_commit_success();
+    assert_eq!(interface.test_account_balance(interface.components.cdp_resource), dec!(3));
+
+    // a single CDP can not be merged, nor can the debts of different rate modes.
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.merge_cdps(&[1u64], bundle).expect_commit_failure();
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.merge_cdps(&[1u64, 3u64], bundle).expect_commit_failure();
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.merge_cdps(&[1u64, 2u64], bundle).expect_commit_success();
+    assert_eq!(interface.test_account_balance(interface.components.cdp_resource), dec!(2));
+    let cdp = interface.get_cdp(1u64);
+    assert_eq!(*cdp.collaterals.get(&xrd_pool.dx_token).unwrap(), dec!(7000));
+    assert_eq!(cdp.debts.get(&usdt).unwrap().total_borrow, dec!(70));
+
+    // the fraction must be between zero and one.
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.split_cdp(1u64, Decimal::ONE, bundle).expect_commit_failure();
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.split_cdp(1u64, dec!("0.5"), bundle).expect_commit_success();
+    assert_eq!(interface.test_account_balance(interface.components.cdp_resource), dec!(3));
+    for cdp_id in [1u64, 4u64] {
+        let cdp = interface.get_cdp(cdp_id);
+        assert_eq!(*cdp.collaterals.get(&xrd_pool.dx_token).unwrap(), dec!(3500));
+        assert_eq!(cdp.debts.get(&usdt).unwrap().total_borrow, dec!(35));
+    }
+}
@@ -0,0 +1,58 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_multi_collateral_multi_debt() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let eth = interface.create_token("ETH");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let usdt_pool = interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let eth_pool = interface.new_pool(eth, ManifestInterestModel::Default, dec!("0.7"), dec!("0.8"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.set_oracle_price(eth, dec!(50000));
+    interface.supply(usdt, dec!(10000)).expect_commit_success();
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+
+    // 5000 XRD (3000 XRD by LTV) backs 125 USDT (2500 XRD).
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(125), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+
+    // 200 USDT (4000 XRD) exceeds the XRD collateral alone.
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.extend_borrow(cdp_id, usdt, dec!(75), false, bundle).expect_commit_failure();
+
+    // a new collateral type requires the proof of the CDP, 0.05 ETH adds 1750 XRD by LTV.
+    interface.addition_collateral(cdp_id, eth, dec!("0.05"), false).expect_commit_failure();
+    interface.addition_collateral(cdp_id, eth, dec!("0.05"), true).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.extend_borrow(cdp_id, usdt, dec!(75), false, bundle).expect_commit_success();
+
+    // the second debt is valued together with the first one: 4000 + 500 <= 4750 XRD.
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.extend_borrow(cdp_id, XRD, dec!(500), false, bundle).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.extend_borrow(cdp_id, XRD, dec!(300), false, bundle).expect_commit_failure();
+
+    let cdp = interface.get_cdp(cdp_id);
+    assert_eq!(cdp.get_collateral_amount(&xrd_pool.dx_token), dec!(5000));
+    assert_eq!(cdp.get_collateral_amount(&eth_pool.dx_token), dec!("0.05"));
+    assert_eq!(cdp.debts.get(&usdt).unwrap().total_borrow, dec!(200));
+    assert_eq!(cdp.debts.get(&XRD).unwrap().total_borrow, dec!(500));
+    assert!(!cdp.debts.get(&usdt).unwrap().is_stable);
+
+    // the ETH collateral can not leave while it backs the debts, the position repaid in USDT can release it.
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.withdraw_collateral(cdp_id, eth_pool.dx_token, dec!("0.05"), bundle).expect_commit_failure();
+    interface.repay(usdt, dec!(200), cdp_id).expect_commit_success();
+    assert!(interface.get_cdp(cdp_id).debts.get(&usdt).unwrap().is_empty());
+    let bundle = interface.price_bundle(&[(eth, "0.00002")]);
+    interface.withdraw_collateral(cdp_id, eth_pool.dx_token, dec!("0.05"), bundle).expect_commit_success();
+
+    let cdp = interface.get_cdp(cdp_id);
+    assert!(!cdp.collaterals.contains_key(&eth_pool.dx_token));
+    assert!(cdp.has_debt());
+    assert_eq!(interface.test_account_balance(usdt_pool.dx_token), dec!(10000));
+}
@@ -0,0 +1,82 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_pause_per_operation() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let usdt_pool = interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(1));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(XRD, dec!(2000)).expect_commit_success();
+    let admin_badge = interface.admin_badge();
+    let guardian_badge = interface.operator_badge();
+
+    // the guardian pauses the borrowing of USDT only.
+    let receipt = interface.pause(guardian_badge, usdt, ManifestOperation::Borrow);
+    let event = interface.parse_event::<common::SetPausedEvent>(&receipt);
+    assert_eq!(event.asset, usdt);
+    assert_eq!(event.operation, common::Operation::Borrow);
+    assert!(event.paused);
+    assert!(interface.is_paused(usdt, ManifestOperation::Borrow));
+    assert!(!interface.is_paused(usdt, ManifestOperation::Supply));
+    assert!(!interface.is_paused(XRD, ManifestOperation::Borrow));
+
+    let bundle = interface.price_bundle(&[(usdt, "1")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(1000), usdt, dec!(100), bundle).expect_commit_failure();
+    interface.supply(usdt, dec!(100)).expect_commit_success();
+    interface.withdraw(usdt_pool.dx_token, dec!(100)).expect_commit_success();
+
+    // only the admin can unpause.
+    interface.unpause(guardian_badge, usdt, ManifestOperation::Borrow).expect_commit_failure();
+    assert!(interface.is_paused(usdt, ManifestOperation::Borrow));
+    interface.unpause(admin_badge, usdt, ManifestOperation::Borrow).expect_commit_success();
+    assert!(!interface.is_paused(usdt, ManifestOperation::Borrow));
+    let bundle = interface.price_bundle(&[(usdt, "1")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(1000), usdt, dec!(100), bundle).expect_commit_success();
+
+    // the admin can pause as well.
+    interface.pause(admin_badge, usdt, ManifestOperation::Supply).expect_commit_success();
+    interface.supply(usdt, dec!(100)).expect_commit_failure();
+    interface.withdraw(usdt_pool.dx_token, dec!(100)).expect_commit_success();
+
+    // the staking operations are paused by the staking earning, not by the lending pools.
+    interface.pause(admin_badge, XRD, ManifestOperation::Join).expect_commit_failure();
+    interface.pause(guardian_badge, XRD, ManifestOperation::Redeem).expect_commit_failure();
+}
+
+#[test]
+fn test_liquidation_while_withdraw_paused() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.supply(usdt, dec!(10000)).expect_commit_success();
+    interface.supply(XRD, dec!(5100)).expect_commit_success();
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(125), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+
+    let guardian_badge = interface.operator_badge();
+    interface.pause(guardian_badge, XRD, ManifestOperation::Withdraw).expect_commit_success();
+    interface.withdraw(xrd_pool.dx_token, dec!(100)).expect_commit_failure();
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.withdraw_collateral(cdp_id, xrd_pool.dx_token, dec!(100), bundle).expect_commit_failure();
+
+    // USDT rises to 33.3 XRD, the released collateral is still redeemed for XRD.
+    interface.pause(guardian_badge, usdt, ManifestOperation::Liquidation).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "0.03")]);
+    interface.liquidation(usdt, dec!(50), Decimal::ZERO, cdp_id, xrd_pool.dx_token, bundle).expect_commit_failure();
+    let admin_badge = interface.admin_badge();
+    interface.unpause(admin_badge, usdt, ManifestOperation::Liquidation).expect_commit_success();
+
+    let xrd_balance = interface.test_account_balance(XRD);
+    let bundle = interface.price_bundle(&[(usdt, "0.03")]);
+    interface.liquidation(usdt, dec!(50), Decimal::ZERO, cdp_id, xrd_pool.dx_token, bundle).expect_commit_success();
+    assert!(interface.test_account_balance(XRD) > xrd_balance + dec!(1666));
+    assert!(interface.get_cdp(cdp_id).get_collateral_amount(&xrd_pool.dx_token) < dec!(5000));
+}
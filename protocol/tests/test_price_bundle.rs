@@ -0,0 +1,41 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_price_bundle_of_position_tokens() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let usdc = interface.create_token("USDC");
+    let eth = interface.create_token("ETH");
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdc, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let eth_pool = interface.new_pool(eth, ManifestInterestModel::Default, dec!("0.7"), dec!("0.8"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.set_oracle_price(usdc, dec!(20));
+    interface.set_oracle_price(eth, dec!(50000));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(usdc, dec!(1000)).expect_commit_success();
+
+    // a quote whose price does not match its signature is rejected.
+    let mut bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    bundle.quotes[1].price = "0.00001".to_owned();
+    interface.borrow_variable(eth_pool.dx_token, dec!("0.1"), usdt, dec!(50), bundle).expect_commit_failure();
+
+    // every token of the position requires a quote.
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(eth_pool.dx_token, dec!("0.1"), usdt, dec!(50), bundle).expect_commit_failure();
+    let bundle = interface.price_bundle(&[(eth, "0.00002"), (usdt, "0.05")]);
+    interface.borrow_variable(eth_pool.dx_token, dec!("0.1"), usdt, dec!(50), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+
+    // the bundle is not limited to two quotes.
+    let bundle = interface.price_bundle(&[(usdc, "0.05"), (eth, "0.00002")]);
+    interface.extend_borrow(cdp_id, usdc, dec!(50), false, bundle).expect_commit_failure();
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (usdc, "0.05"), (eth, "0.00002")]);
+    interface.extend_borrow(cdp_id, usdc, dec!(50), false, bundle).expect_commit_success();
+
+    let cdp = interface.get_cdp(cdp_id);
+    assert_eq!(cdp.debts.get(&usdt).unwrap().total_borrow, dec!(50));
+    assert_eq!(cdp.debts.get(&usdc).unwrap().total_borrow, dec!(50));
+}
@@ -0,0 +1,39 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_rebalance_stable_rate() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let eth = interface.create_token("ETH");
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let eth_pool = interface.new_pool(eth, ManifestInterestModel::Default, dec!("0.7"), dec!("0.8"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.set_oracle_price(eth, dec!(50000));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(eth, dec!(2)).expect_commit_success();
+
+    // the stable rate is locked in at 10% utilization.
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.borrow_stable(eth_pool.dx_token, dec!("0.5"), usdt, dec!(100), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+    let locked_rate = interface.get_cdp(cdp_id).debts.get(&usdt).unwrap().stable_rate;
+    interface.rebalance_stable_rate(cdp_id, usdt).expect_commit_failure();
+
+    // at 80% utilization the supply rate exceeds the locked stable rate.
+    let bundle = interface.price_bundle(&[(usdt, "0.05"), (eth, "0.00002")]);
+    interface.borrow_variable(eth_pool.dx_token, dec!(1), usdt, dec!(700), bundle).expect_commit_success();
+    interface.advance_epochs(100);
+    interface.rebalance_stable_rate(cdp_id, usdt).expect_commit_success();
+
+    let debt = interface.get_cdp(cdp_id).debts.get(&usdt).cloned().unwrap();
+    assert!(debt.is_stable);
+    assert!(debt.stable_rate > locked_rate);
+    assert!(debt.borrow_amount > dec!(100));
+    assert_eq!(debt.last_update_epoch, interface.current_epoch());
+    assert_eq!(debt.total_borrow, dec!(100));
+
+    // the debt is already at the current stable rate.
+    interface.rebalance_stable_rate(cdp_id, usdt).expect_commit_failure();
+}
@@ -0,0 +1,51 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_risk_param_change_after_timelock() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.supply(usdt, dec!(10000)).expect_commit_success();
+    interface.supply(XRD, dec!(10000)).expect_commit_success();
+
+    // the liquidation threshold must stay below one.
+    interface.queue_risk_param_change(XRD, ManifestRiskParam::LiquidationThreshold(Decimal::ONE)).expect_commit_failure();
+
+    let receipt = interface.queue_risk_param_change(XRD, ManifestRiskParam::Ltv(dec!("0.3")));
+    let event = interface.parse_event::<QueueRiskParamChangeEvent>(&receipt);
+    let change_id = event.change_id;
+    assert_eq!(event.effective_epoch, interface.current_epoch() + 288);
+    interface.execute_risk_param_change(change_id).expect_commit_failure();
+
+    // the LTV is still 0.6 until the change is executed.
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(100), bundle).expect_commit_success();
+
+    interface.advance_epochs(288);
+    interface.execute_risk_param_change(change_id).expect_commit_success();
+    interface.execute_risk_param_change(change_id).expect_commit_failure();
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(100), bundle).expect_commit_failure();
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(70), bundle).expect_commit_success();
+
+    // a cancelled change can not be executed.
+    let receipt = interface.queue_risk_param_change(XRD, ManifestRiskParam::Ltv(dec!("0.6")));
+    let change_id = interface.parse_event::<QueueRiskParamChangeEvent>(&receipt).change_id;
+    interface.cancel_risk_param_change(change_id).expect_commit_success();
+    interface.advance_epochs(288);
+    interface.execute_risk_param_change(change_id).expect_commit_failure();
+
+    // only the authority can shorten the timelock.
+    let operator_badge = interface.operator_badge();
+    let admin_badge = interface.admin_badge();
+    interface.set_risk_param_timelock(operator_badge, 0u64).expect_commit_failure();
+    interface.set_risk_param_timelock(admin_badge, 0u64).expect_commit_success();
+    let receipt = interface.queue_risk_param_change(usdt, ManifestRiskParam::FlashloanFeeRatio(dec!("0.01")));
+    let change_id = interface.parse_event::<QueueRiskParamChangeEvent>(&receipt).change_id;
+    interface.execute_risk_param_change(change_id).expect_commit_success();
+}
@@ -0,0 +1,37 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_lsu_as_collateral() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    let (_validator, lsu) = interface.new_staked_validator();
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(lsu, dec!(1000), usdt, dec!(20), bundle).expect_commit_failure();
+
+    // a token which is neither LSU nor dseXRD is refused.
+    interface.set_staking_collateral(usdt, dec!("0.5"), dec!("0.6"), dec!("0.05")).expect_commit_failure();
+    interface.set_staking_collateral(lsu, dec!("0.5"), dec!("0.6"), dec!("0.05")).expect_commit_success();
+
+    // 1000 LSU (500 XRD by LTV) backs 20 USDT (400 XRD).
+    let lsu_balance = interface.test_account_balance(lsu);
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(lsu, dec!(1000), usdt, dec!(20), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+    assert_eq!(interface.get_cdp(cdp_id).get_collateral_amount(&lsu), dec!(1000));
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.extend_borrow(cdp_id, usdt, dec!(10), false, bundle).expect_commit_failure();
+
+    // the LSU is handed back as it is.
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.withdraw_collateral(cdp_id, lsu, dec!(100), bundle).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.withdraw_collateral(cdp_id, lsu, dec!(150), bundle).expect_commit_failure();
+    assert_eq!(interface.get_cdp(cdp_id).get_collateral_amount(&lsu), dec!(900));
+    assert_eq!(interface.test_account_balance(lsu), lsu_balance - dec!(900));
+}
@@ -0,0 +1,39 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_swap_rate_mode() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let usdt_pool = interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(20));
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(XRD, dec!(5000)).expect_commit_success();
+
+    let bundle = interface.price_bundle(&[(usdt, "0.05")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(5000), usdt, dec!(100), bundle).expect_commit_success();
+    let cdp_id = 1u64;
+    interface.swap_rate_mode(cdp_id, XRD).expect_commit_failure();
+
+    // the variable debt becomes a stable loan of the same amount.
+    interface.swap_rate_mode(cdp_id, usdt).expect_commit_success();
+    let debt = interface.get_cdp(cdp_id).debts.get(&usdt).cloned().unwrap();
+    assert!(debt.is_stable);
+    assert_eq!(debt.normalized_borrow, Decimal::ZERO);
+    assert_eq!(debt.borrow_amount, dec!(100));
+    assert!(debt.stable_rate.is_positive());
+    assert_eq!(debt.last_update_epoch, interface.current_epoch());
+
+    // the stable interest is capitalized into the variable loan shares.
+    interface.advance_epochs(1000);
+    interface.swap_rate_mode(cdp_id, usdt).expect_commit_success();
+    let debt = interface.get_cdp(cdp_id).debts.get(&usdt).cloned().unwrap();
+    assert!(!debt.is_stable);
+    assert_eq!(debt.borrow_amount, Decimal::ZERO);
+    assert_eq!(debt.stable_rate, Decimal::ZERO);
+    let (_, loan_index) = interface.get_current_index(&usdt_pool);
+    assert!(debt.normalized_borrow * loan_index > dec!(100));
+    assert_eq!(debt.total_borrow, dec!(100));
+}
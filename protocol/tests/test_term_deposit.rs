@@ -0,0 +1,73 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+/// Creates the USDT pool with half of the supply borrowed, so the term deposits are quoted a positive rate.
+fn setup_borrowed_pool(interface: &mut ProtocolInterface) -> LendPool{
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let usdt_pool = interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(1));
+
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(XRD, dec!(1000)).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "1")]);
+    interface.borrow_variable(xrd_pool.dx_token, dec!(1000), usdt, dec!(500), bundle).expect_commit_success();
+    usdt_pool
+}
+
+fn expected_interest(principal: Decimal, fixed_rate: Decimal, periods: u64) -> Decimal{
+    principal * common::calc_linear_rate(fixed_rate, Decimal::from(common::EPOCH_OF_YEAR), periods)
+}
+
+#[test]
+fn test_term_deposit_redeem_after_maturity() {
+    let mut interface = get_setup();
+    let usdt_pool = setup_borrowed_pool(&mut interface);
+    let usdt = usdt_pool.underlying_token;
+
+    interface.deposit_term(usdt, dec!(1), 2).expect_commit_failure();
+    let receipt = interface.deposit_term(usdt, dec!(100), 1);
+    let event = interface.parse_event::<lending::DepositTermEvent>(&receipt);
+    assert!(event.fixed_rate.is_positive());
+    let term_deposit: lending::TermDeposit = interface.ledger.get_non_fungible_data(usdt_pool.term_deposit_resource, event.deposit_id.clone());
+    let day = common::EPOCH_OF_YEAR / 365;
+    assert_eq!(term_deposit.maturity_point % day, 0);
+    assert!(term_deposit.maturity_point >= term_deposit.start_point + 7 * day);
+
+    // the interest stops at the maturity, the extra week is not paid.
+    let current_epoch = interface.current_epoch();
+    interface.advance_epochs(term_deposit.maturity_point - current_epoch + 7 * day);
+    let usdt_balance = interface.test_account_balance(usdt);
+    let receipt = interface.redeem_term_deposit(&usdt_pool, event.deposit_id);
+    let redeem = interface.parse_event::<lending::RedeemTermDepositEvent>(&receipt);
+    let interest = expected_interest(dec!(100), event.fixed_rate, term_deposit.maturity_point - term_deposit.start_point);
+    assert_eq!(redeem.principal, dec!(100));
+    assert_eq!(redeem.interest, interest);
+    assert_eq!(redeem.penalty, Decimal::ZERO);
+    assert_eq!(interface.test_account_balance(usdt), usdt_balance + dec!(100) + interest);
+    assert_eq!(interface.test_account_balance(usdt_pool.term_deposit_resource), Decimal::ZERO);
+}
+
+#[test]
+fn test_term_deposit_early_exit() {
+    let mut interface = get_setup();
+    let usdt_pool = setup_borrowed_pool(&mut interface);
+    let usdt = usdt_pool.underlying_token;
+
+    let receipt = interface.deposit_term(usdt, dec!(100), 4);
+    let event = interface.parse_event::<lending::DepositTermEvent>(&receipt);
+    let term_deposit: lending::TermDeposit = interface.ledger.get_non_fungible_data(usdt_pool.term_deposit_resource, event.deposit_id.clone());
+
+    // halfway through the term, the accrued interest is paid less 1% of the principal.
+    let elapsed = (term_deposit.maturity_point - term_deposit.start_point) / 2;
+    interface.advance_epochs(elapsed);
+    let usdt_balance = interface.test_account_balance(usdt);
+    let receipt = interface.redeem_term_deposit(&usdt_pool, event.deposit_id);
+    let redeem = interface.parse_event::<lending::RedeemTermDepositEvent>(&receipt);
+    let interest = expected_interest(dec!(100), event.fixed_rate, elapsed);
+    assert_eq!(redeem.principal, dec!(100));
+    assert_eq!(redeem.interest, interest);
+    assert_eq!(redeem.penalty, dec!(1));
+    assert_eq!(interface.test_account_balance(usdt), usdt_balance + dec!(100) + interest - dec!(1));
+}
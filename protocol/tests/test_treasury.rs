@@ -0,0 +1,57 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_treasury_spending_approval() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.supply(usdt, dec!(10000)).expect_commit_success();
+    let admin_badge = interface.admin_badge();
+    let operator_badge = interface.operator_badge();
+
+    // the whole flash loan fee goes to the treasury share of the pool, then into the treasury.
+    interface.set_flashloan_fee_split(usdt, Decimal::ZERO, Decimal::ONE).expect_commit_success();
+    interface.flashloan(usdt, dec!(1000), dec!(3), false).expect_commit_success();
+    interface.withdraw_treasury(usdt, dec!(4)).expect_commit_failure();
+    let receipt = interface.withdraw_treasury(usdt, dec!(3));
+    let deposit = interface.parse_event::<DepositRevenueEvent>(&receipt);
+    assert_eq!(deposit.source, RevenueSource::FlashLoanFee);
+    assert_eq!(deposit.amount, dec!(3));
+    assert_eq!(interface.treasury_balance(usdt), dec!(3));
+    assert_eq!(interface.treasury_revenues(usdt).get(&RevenueSource::FlashLoanFee).copied(), Some(dec!(3)));
+
+    let (_, _, recipient) = interface.ledger.new_allocated_account();
+    let cdp_component = interface.components.cdp_component;
+    interface.propose_spending(operator_badge, usdt, dec!(2), cdp_component, "audit").expect_commit_failure();
+    let receipt = interface.propose_spending(operator_badge, usdt, dec!(2), recipient, "audit");
+    let spending_id: u64 = receipt.expect_commit_success().output(2);
+    let pending = interface.pending_spendings();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending.get(&spending_id).unwrap().amount, dec!(2));
+
+    // the funds are released only by the admin.
+    interface.approve_spending(operator_badge, spending_id).expect_commit_failure();
+    assert_eq!(interface.treasury_balance(usdt), dec!(3));
+    let receipt = interface.approve_spending(admin_badge, spending_id);
+    let approve = interface.parse_event::<ApproveSpendingEvent>(&receipt);
+    assert_eq!(approve.recipient, recipient);
+    assert_eq!(approve.balance, dec!(1));
+    assert_eq!(interface.ledger.get_component_balance(recipient, usdt), dec!(2));
+    assert_eq!(interface.treasury_balance(usdt), dec!(1));
+    assert!(interface.pending_spendings().is_empty());
+    interface.approve_spending(admin_badge, spending_id).expect_commit_failure();
+
+    // the spending beyond the balance can not be approved, the admin rejects it.
+    let receipt = interface.propose_spending(operator_badge, usdt, dec!(5), recipient, "grant");
+    let spending_id: u64 = receipt.expect_commit_success().output(2);
+    interface.approve_spending(admin_badge, spending_id).expect_commit_failure();
+    interface.reject_spending(operator_badge, spending_id).expect_commit_failure();
+    assert_eq!(interface.pending_spendings().len(), 1);
+    let receipt = interface.reject_spending(admin_badge, spending_id);
+    assert_eq!(interface.parse_event::<RejectSpendingEvent>(&receipt).spending_id, spending_id);
+    assert!(interface.pending_spendings().is_empty());
+    assert_eq!(interface.ledger.get_component_balance(recipient, usdt), dec!(2));
+    assert_eq!(interface.treasury_balance(usdt), dec!(1));
+}
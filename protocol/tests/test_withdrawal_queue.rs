@@ -0,0 +1,101 @@
+#[path = "tests_common/mod.rs"]
+mod tests_common;
+use tests_common::*;
+
+#[test]
+fn test_withdrawal_queue_fill_order() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let usdt_pool = interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(1));
+
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(XRD, dec!(2000)).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "1")]);
+    let receipt = interface.borrow_variable(xrd_pool.dx_token, dec!(2000), usdt, dec!(900), bundle);
+    let cdp_id = interface.parse_event::<BorrowEvent>(&receipt).cdp_id;
+
+    // only 100 USDT is left in the vault, the first request takes all of it.
+    let receipt = interface.request_withdrawal(usdt_pool.dx_token, dec!(300));
+    let request_a = interface.parse_event::<lending::RequestWithdrawalEvent>(&receipt).request_id;
+    let fills = interface.parse_events::<lending::FillWithdrawalEvent>(&receipt);
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0].request_id, request_a);
+    assert_eq!(fills[0].amount, dec!(100));
+    assert_eq!(fills[0].unfilled, dec!(200));
+
+    let receipt = interface.request_withdrawal(usdt_pool.dx_token, dec!(200));
+    let request_b = interface.parse_event::<lending::RequestWithdrawalEvent>(&receipt).request_id;
+    assert!(interface.parse_events::<lending::FillWithdrawalEvent>(&receipt).is_empty());
+
+    // the repayment completes the older request before the newer one gets anything.
+    let receipt = interface.repay(usdt, dec!(250), cdp_id);
+    let fills = interface.parse_events::<lending::FillWithdrawalEvent>(&receipt);
+    assert_eq!(fills.len(), 2);
+    assert_eq!(fills[0].request_id, request_a);
+    assert_eq!(fills[0].amount, dec!(200));
+    assert_eq!(fills[0].unfilled, Decimal::ZERO);
+    assert_eq!(fills[1].request_id, request_b);
+    assert_eq!(fills[1].amount, dec!(50));
+    assert_eq!(fills[1].unfilled, dec!(150));
+
+    // the fully filled request is burnt on the claim.
+    let usdt_balance = interface.test_account_balance(usdt);
+    let receipt = interface.claim_withdrawal(&usdt_pool, request_a.clone());
+    let claim = interface.parse_event::<lending::ClaimWithdrawalEvent>(&receipt);
+    assert_eq!(claim.amount, dec!(300));
+    assert_eq!(claim.remaining, Decimal::ZERO);
+    assert_eq!(interface.test_account_balance(usdt), usdt_balance + dec!(300));
+    assert_eq!(interface.test_account_balance(usdt_pool.withdrawal_resource), dec!(1));
+
+    // the partially filled request is returned for the rest.
+    let usdt_balance = interface.test_account_balance(usdt);
+    let receipt = interface.claim_withdrawal(&usdt_pool, request_b.clone());
+    let claim = interface.parse_event::<lending::ClaimWithdrawalEvent>(&receipt);
+    assert_eq!(claim.amount, dec!(50));
+    assert_eq!(claim.remaining, dec!(150));
+    assert_eq!(interface.test_account_balance(usdt), usdt_balance + dec!(50));
+    assert_eq!(interface.test_account_balance(usdt_pool.withdrawal_resource), dec!(1));
+    let request: lending::WithdrawalRequest = interface.ledger.get_non_fungible_data(usdt_pool.withdrawal_resource, request_b);
+    assert_eq!(request.amount, dec!(150));
+    assert_eq!(request.filled, Decimal::ZERO);
+}
+
+#[test]
+fn test_withdrawal_queue_goes_before_direct_withdraw() {
+    let mut interface = get_setup();
+    let usdt = interface.create_token("USDT");
+    let xrd_pool = interface.new_pool(XRD, ManifestInterestModel::Default, dec!("0.6"), dec!("0.7"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    let usdt_pool = interface.new_pool(usdt, ManifestInterestModel::StableCoin, dec!("0.8"), dec!("0.85"), dec!("0.05"), dec!("0.1"), dec!("0.003"));
+    interface.set_oracle_price(usdt, dec!(1));
+
+    interface.supply(usdt, dec!(1000)).expect_commit_success();
+    interface.supply(XRD, dec!(2000)).expect_commit_success();
+    let bundle = interface.price_bundle(&[(usdt, "1")]);
+    let receipt = interface.borrow_variable(xrd_pool.dx_token, dec!(2000), usdt, dec!(900), bundle);
+    let cdp_id = interface.parse_event::<BorrowEvent>(&receipt).cdp_id;
+
+    let receipt = interface.request_withdrawal(usdt_pool.dx_token, dec!(300));
+    let request_id = interface.parse_event::<lending::RequestWithdrawalEvent>(&receipt).request_id;
+
+    // the liquidity of the repayment goes to the queued request, not to the direct withdrawal.
+    interface.repay(usdt, dec!(150), cdp_id).expect_commit_success();
+    interface.withdraw(usdt_pool.dx_token, dec!(10)).expect_commit_failure();
+
+    // the unfilled part is deposited back once the request is cancelled.
+    let dx_balance = interface.test_account_balance(usdt_pool.dx_token);
+    let usdt_balance = interface.test_account_balance(usdt);
+    let receipt = interface.cancel_withdrawal(&usdt_pool, request_id);
+    let cancel = interface.parse_event::<lending::CancelWithdrawalEvent>(&receipt);
+    assert_eq!(cancel.amount, dec!(250));
+    assert_eq!(interface.test_account_balance(usdt), usdt_balance + dec!(250));
+    assert_eq!(interface.test_account_balance(usdt_pool.dx_token), dx_balance + cancel.dx_amount);
+    assert_eq!(interface.test_account_balance(usdt_pool.withdrawal_resource), Decimal::ZERO);
+
+    // the cancelled request no longer holds up the direct withdrawal.
+    interface.repay(usdt, dec!(100), cdp_id).expect_commit_success();
+    let usdt_balance = interface.test_account_balance(usdt);
+    interface.withdraw(usdt_pool.dx_token, dec!(10)).expect_commit_success();
+    assert_eq!(interface.test_account_balance(usdt), usdt_balance + dec!(10));
+}
@@ -1,8 +1,7 @@
 #![allow(dead_code)]
 
 use scrypto_test::prelude::*;
-use super::Resources;
-use ::common::*;
+use super::{Resources, price_signer_public_key};
 
 use std::path::Path;
 
@@ -18,9 +17,9 @@ fn check_compile(
 
     if wasm_path.exists() && rpd_path.exists() {
         let code = std::fs::read(&wasm_path).expect("failed to read WASM file");
-        let definition: PackageDefinition = manifest_decode(
+        let definition: PackageDefinition = manifest_decode::<ManifestPackageDefinition>(
             &std::fs::read(&rpd_path).expect("Failed to read RPD file")
-        ).expect("Failed to decode RPD file");
+        ).expect("Failed to decode RPD file").try_into_typed().expect("Failed to decode RPD file");
         return (code, definition);
     } else {
         let (code, definition) = Compile::compile_with_env_vars(
@@ -53,7 +52,12 @@ pub struct Components {
     pub protocol_package: PackageAddress,
     pub cdp_component: ComponentAddress,
     pub earning_component: ComponentAddress,
-
+    pub treasury_component: ComponentAddress,
+    pub cdp_resource: ResourceAddress,
+    pub flashloan_resource: ResourceAddress,
+    pub batch_flashloan_resource: ResourceAddress,
+    pub fee_exempt_resource: ResourceAddress,
+    pub delegation_resource: ResourceAddress,
 }
 
 pub fn create_components(
@@ -74,11 +78,12 @@ pub fn create_components(
         "BASE_RESOURCE".to_owned() => resources.base_resource.to_string(encoder),
     };
 
-    let pub_key_str = "a5bc3d9296bda1e52f96bf0a65238998877dbddb0703bd37ef1f18a6ffce458a";
-    let (keeper_package, keeper_component) = create_keeper(resources, envs, use_coverage, encoder, ledger);
-    let (interest_package, interest_component) = create_interest(resources, envs, use_coverage, encoder, ledger);
-    let (oracle_package, oracle_component) = create_oracle(pub_key_str, resources, envs, use_coverage, encoder, ledger);
-    let (protocol_package, earning_component, cdp_component) = create_protocol(resources, envs, use_coverage, encoder, ledger);
+    let pub_key_str = price_signer_public_key();
+    let authority = (account, public_key);
+    let (keeper_package, keeper_component) = create_keeper(authority, resources, envs, use_coverage, encoder, ledger);
+    let (interest_package, interest_component) = create_interest(authority, resources, envs, use_coverage, encoder, ledger);
+    let (oracle_package, oracle_component) = create_oracle(authority, &pub_key_str, resources, envs, use_coverage, encoder, ledger);
+    let (protocol_package, earning_component, cdp_component, treasury_component, cdp_resource, flashloan_resource, batch_flashloan_resource, fee_exempt_resource, delegation_resource) = create_protocol(authority, resources, envs, use_coverage, encoder, ledger);
     Components { 
         keeper_package, 
         keeper_component, 
@@ -88,11 +93,36 @@ pub fn create_components(
         oracle_component,
         protocol_package,
         cdp_component,
-        earning_component
+        earning_component,
+        treasury_component,
+        cdp_resource,
+        flashloan_resource,
+        batch_flashloan_resource,
+        fee_exempt_resource,
+        delegation_resource
      }
 }
 
+/// The `instantiate` functions require the authority badge, which is held by the test account.
+fn instantiate_with_authority(
+    authority: (ComponentAddress, Secp256k1PublicKey),
+    resources: &Resources,
+    package_address: PackageAddress,
+    blueprint_name: &str,
+    args: ManifestArgs,
+    ledger: &mut LedgerSimulator<NoExtension, InMemorySubstateDatabase>
+) -> TransactionReceipt{
+    let (account, public_key) = authority;
+    let manifest = ManifestBuilder::new()
+        .lock_fee_from_faucet()
+        .create_proof_from_account_of_amount(account, resources.authority_resource, dec!(1))
+        .call_function(package_address, blueprint_name, "instantiate", args)
+        .build();
+    ledger.execute_manifest(manifest, vec![NonFungibleGlobalId::from_public_key(&public_key)])
+}
+
 fn create_keeper(
+    authority: (ComponentAddress, Secp256k1PublicKey),
     resources: &Resources,
     envs: &mut BTreeMap<String, String>,
     use_coverage: bool,
@@ -105,11 +135,13 @@ fn create_keeper(
         resources.owner_role.clone()
     );
 
-    let keeper_component = ledger.call_function(
+    let keeper_component = instantiate_with_authority(
+        authority,
+        resources,
         keeper_package,
         "ValidatorKeeper",
-        "instantiate", 
-        manifest_args!(resources.owner_role.clone())
+        manifest_args!(resources.owner_role.clone()),
+        ledger
     ).expect_commit_success().new_component_addresses()[0];
 
     envs.insert("KEEPER_PACKAGE".to_owned(), keeper_package.to_string(encoder));
@@ -119,6 +151,7 @@ fn create_keeper(
 }
 
 fn create_interest(
+    authority: (ComponentAddress, Secp256k1PublicKey),
     resources: &Resources,
     envs: &mut BTreeMap<String, String>,
     use_coverage: bool,
@@ -131,11 +164,13 @@ fn create_interest(
         resources.owner_role.clone()
     );
 
-    let interest_component = ledger.call_function(
+    let interest_component = instantiate_with_authority(
+        authority,
+        resources,
         interest_package,
         "DefInterestModel",
-        "instantiate", 
-        manifest_args!(resources.owner_role.clone(), dec!("0.2"), dec!("0.5"), dec!("0.55"), dec!("0.45"))
+        manifest_args!(resources.owner_role.clone(), dec!("0.2"), dec!("0.5"), dec!("0.55"), dec!("0.45")),
+        ledger
     ).expect_commit_success().new_component_addresses()[0];
 
     envs.insert("INTEREST_PACKAGE".to_owned(), interest_package.to_string(encoder));
@@ -145,6 +180,7 @@ fn create_interest(
 }
 
 fn create_oracle(
+    authority: (ComponentAddress, Secp256k1PublicKey),
     pub_key_str: &str,
     resources: &Resources,
     envs: &mut BTreeMap<String, String>,
@@ -159,11 +195,13 @@ fn create_oracle(
     );
 
 
-    let oracle_component = ledger.call_function(
+    let oracle_component = instantiate_with_authority(
+        authority,
+        resources,
         oracle_package,
         "PriceOracle",
-        "instantiate", 
-        manifest_args!(resources.owner_role.clone(),  pub_key_str, 3000u64)
+        manifest_args!(resources.owner_role.clone(),  pub_key_str, 3000u64),
+        ledger
     ).expect_commit_success().new_component_addresses()[0];
 
     envs.insert("ORACLE_PACKAGE".to_owned(), oracle_package.to_string(encoder));
@@ -172,45 +210,63 @@ fn create_oracle(
     (oracle_package, oracle_component)
 }
 
+// (package, earning, cdp, treasury, cdp resource, flashloan resource, batch flashloan resource, fee exempt badge, credit delegation badge)
+type ProtocolAddresses = (PackageAddress, ComponentAddress, ComponentAddress, ComponentAddress, ResourceAddress, ResourceAddress, ResourceAddress, ResourceAddress, ResourceAddress);
+
 fn create_protocol(
+    authority: (ComponentAddress, Secp256k1PublicKey),
     resources: &Resources,
     envs: &mut BTreeMap<String, String>,
     use_coverage: bool,
     encoder: &AddressBech32Encoder,
     ledger: &mut LedgerSimulator<NoExtension, InMemorySubstateDatabase>
-) -> (PackageAddress, ComponentAddress, ComponentAddress){
+) -> ProtocolAddresses{
     let protocol_package = ledger.publish_package(
         check_compile("../protocol", "protocol", envs, use_coverage), 
         BTreeMap::new(),
         resources.owner_role.clone()
     );
 
-    
-    let binding = ledger.call_function(
+    // the staking pool is instantiated together with the earning, so take the returned component.
+    let earning_component: ComponentAddress = instantiate_with_authority(
+        authority,
+        resources,
         protocol_package,
         "StakingEarning",
-        "instantiate", 
-        manifest_args!(resources.owner_role.clone())
+        manifest_args!(resources.owner_role.clone()),
+        ledger
+    ).expect_commit_success().output(2);
+
+    let receipt = instantiate_with_authority(
+        authority,
+        resources,
+        protocol_package,
+        "CollateralDebtManager",
+        manifest_args!(resources.owner_role.clone(), earning_component),
+        ledger
     );
-    let component_addresses = binding.expect_commit_success().new_component_addresses();
-    
-    let staking_pool = component_addresses[0];
-    let earning_component = component_addresses[1];
-
-    let cdp_component = ledger.call_function(
-        protocol_package, 
-        "CollateralDebtManager", 
-        "instantiate",
-        manifest_args!(
-            resources.owner_role.clone() //,
-            // rule!(require(global_caller(earning_component)))       
-        )
-    ).expect_commit_success().new_component_addresses()[0];
-    
+    let result = receipt.expect_commit_success();
+    let cdp_component: ComponentAddress = result.output(2);
+    let cdp_resource = result.new_resource_addresses().iter().copied().find(|res_addr|
+        ledger.get_metadata((*res_addr).into(), "symbol") == Some(MetadataValue::String("CDP".to_owned()))
+    ).expect("CDP resource not found");
+    let flashloan_resource = result.new_resource_addresses().iter().copied().find(|res_addr|
+        ledger.get_metadata((*res_addr).into(), "name") == Some(MetadataValue::String("dxLoanNFT".to_owned()))
+    ).expect("flashloan resource not found");
+    let batch_flashloan_resource = result.new_resource_addresses().iter().copied().find(|res_addr|
+        ledger.get_metadata((*res_addr).into(), "name") == Some(MetadataValue::String("dxBatchLoanNFT".to_owned()))
+    ).expect("batch flashloan resource not found");
+    let fee_exempt_resource = result.new_resource_addresses().iter().copied().find(|res_addr|
+        ledger.get_metadata((*res_addr).into(), "symbol") == Some(MetadataValue::String("dxFEB".to_owned()))
+    ).expect("fee exempt badge not found");
+    let delegation_resource = result.new_resource_addresses().iter().copied().find(|res_addr|
+        ledger.get_metadata((*res_addr).into(), "symbol") == Some(MetadataValue::String("dxCD".to_owned()))
+    ).expect("credit delegation badge not found");
+    let treasury_component: ComponentAddress = ledger.call_method(cdp_component, "get_treasury", manifest_args!()).expect_commit_success().output(1);
     
     envs.insert("PROTOCOL_PACKAGE".to_owned(), protocol_package.to_string(encoder));
     envs.insert("EARNING_COMPONENT".to_owned(), earning_component.to_string(encoder));
     envs.insert("CDP_COMPONENT".to_owned(), cdp_component.to_string(encoder));
 
-    (protocol_package, earning_component, cdp_component)
-}
\ No newline at end of file
+    (protocol_package, earning_component, cdp_component, treasury_component, cdp_resource, flashloan_resource, batch_flashloan_resource, fee_exempt_resource, delegation_resource)
+}
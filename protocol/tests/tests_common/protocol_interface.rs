@@ -1,8 +1,18 @@
-#[warn(dead_code)]
+#![allow(dead_code)]
 
 use scrypto_test::prelude::*;
 use super::*;
 
+/// The lending pool of an asset and the resources it defines.
+#[derive(Clone, Copy)]
+pub struct LendPool{
+    pub underlying_token: ResourceAddress,
+    pub pool_component: ComponentAddress,
+    pub dx_token: ResourceAddress,
+    pub withdrawal_resource: ResourceAddress,
+    pub term_deposit_resource: ResourceAddress
+}
+
 pub struct ProtocolInterface{
     pub public_key: Secp256k1PublicKey,
     pub test_account: ComponentAddress,
@@ -21,5 +31,845 @@ impl ProtocolInterface {
     ) -> Self{
         Self { public_key, resources, components, ledger, test_account: account }
     }
-    
-}
\ No newline at end of file
+
+    pub fn execute(&mut self, manifest: TransactionManifestV1) -> TransactionReceipt{
+        self.ledger.execute_manifest(manifest, vec![NonFungibleGlobalId::from_public_key(&self.public_key)])
+    }
+
+    /// admin: the authority badge, operator: the base authority badge.
+    fn manifest_with_badge(&self, badge: ResourceAddress) -> ManifestBuilder{
+        ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_proof_from_account_of_amount(self.test_account, badge, dec!(1))
+    }
+
+    pub fn admin_badge(&self) -> ResourceAddress{
+        self.resources.authority_resource
+    }
+
+    pub fn operator_badge(&self) -> ResourceAddress{
+        self.resources.base_authority_resource
+    }
+
+    pub fn test_account_balance(&mut self, res_addr: ResourceAddress) -> Decimal{
+        self.ledger.get_component_balance(self.test_account, res_addr)
+    }
+
+    pub fn parse_events<T: ScryptoEvent>(&self, receipt: &TransactionReceipt) -> Vec<T>{
+        self.ledger.extract_events_of_type::<T>(receipt.expect_commit_success())
+    }
+
+    pub fn parse_event<T: ScryptoEvent>(&self, receipt: &TransactionReceipt) -> T{
+        self.parse_events::<T>(receipt).pop().expect("event not found")
+    }
+
+    pub fn fund_xrd(&mut self){
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .get_free_xrd_from_faucet()
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest).expect_commit_success();
+    }
+
+    pub fn create_token(&mut self, symbol: &str) -> ResourceAddress{
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .create_fungible_resource(
+                OwnerRole::None,
+                true,
+                DIVISIBILITY_MAXIMUM,
+                FungibleResourceRoles::default(),
+                metadata!(init{
+                    "symbol" => symbol.to_owned(), locked;
+                }),
+                Some(dec!(1000000000))
+            )
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest).expect_commit_success().new_resource_addresses()[0]
+    }
+
+    pub fn advance_epochs(&mut self, epochs: u64){
+        let current_epoch = self.ledger.get_current_epoch();
+        self.ledger.set_current_epoch(Epoch::of(current_epoch.number() + epochs));
+    }
+
+    pub fn advance_seconds(&mut self, seconds: u64){
+        let round = self.ledger.get_consensus_manager_state().round.number() + 1;
+        let timestamp_ms = self.ledger.get_current_proposer_timestamp_ms() + (seconds as i64) * 1000;
+        self.ledger.advance_to_round_at_timestamp(Round::of(round), timestamp_ms).expect_commit_success();
+    }
+
+    pub fn current_epoch(&mut self) -> u64{
+        self.ledger.get_current_epoch().number()
+    }
+
+    fn get_metadata_address(&mut self, address: GlobalAddress, key: &str) -> GlobalAddress{
+        match self.ledger.get_metadata(address, key) {
+            Some(MetadataValue::GlobalAddress(addr)) => addr,
+            _ => panic!("metadata {} not found", key)
+        }
+    }
+
+    fn find_resource_by_name(&mut self, res_addrs: &[ResourceAddress], prefix: &str) -> ResourceAddress{
+        res_addrs.iter().copied().find(|res_addr| matches!(
+            self.ledger.get_metadata((*res_addr).into(), "name"),
+            Some(MetadataValue::String(name)) if name.starts_with(prefix)
+        )).unwrap_or_else(|| panic!("resource {} not found", prefix))
+    }
+
+    // ---------------------------------------------------------------- oracle
+
+    /// Registers the quote token in the oracle, the signed quotes are only accepted for registered tokens.
+    pub fn set_oracle_price(&mut self, res_addr: ResourceAddress, price_in_xrd: Decimal){
+        let manifest = self.manifest_with_badge(self.operator_badge())
+            .call_method(self.components.oracle_component, "set_price_quote_in_xrd", manifest_args!(res_addr, price_in_xrd))
+            .build();
+        self.execute(manifest).expect_commit_success();
+    }
+
+    /// Signs the XRD prices in each quote token (I.E.: "0.05" means 1 XRD = 0.05 USDT) for current epoch.
+    pub fn price_bundle(&mut self, prices: &[(ResourceAddress, &str)]) -> ManifestPriceBundle{
+        let epoch = self.current_epoch();
+        let timestamp = self.ledger.get_current_proposer_timestamp_ms() as u64;
+        ManifestPriceBundle{
+            quotes: prices.iter().map(|(quote, price)| sign_price_quote(*quote, price, epoch, timestamp)).collect()
+        }
+    }
+
+    // ---------------------------------------------------------------- cdp admin
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_pool(&mut self,
+        underlying_token: ResourceAddress,
+        interest_model: ManifestInterestModel,
+        ltv: Decimal,
+        liquidation_threshold: Decimal,
+        liquidation_bonus: Decimal,
+        insurance_ratio: Decimal,
+        flashloan_fee_ratio: Decimal
+    ) -> LendPool{
+        let manifest = self.manifest_with_badge(self.admin_badge())
+            .call_method(self.components.cdp_component, "new_pool", manifest_args!(
+                self.resources.owner_role.clone(),
+                18u8,
+                underlying_token,
+                interest_model,
+                ltv,
+                liquidation_threshold,
+                liquidation_bonus,
+                insurance_ratio,
+                flashloan_fee_ratio
+            ))
+            .build();
+        let receipt = self.execute(manifest);
+        let result = receipt.expect_commit_success();
+        let dx_token: ResourceAddress = result.output(2);
+        let new_resources: Vec<ResourceAddress> = result.new_resource_addresses().iter().copied().collect();
+
+        let pool_component = ComponentAddress::try_from(self.get_metadata_address(dx_token.into(), "pool")).unwrap();
+        let withdrawal_resource = self.find_resource_by_name(&new_resources, "DeXian Withdrawal Request");
+        let term_deposit_resource = self.find_resource_by_name(&new_resources, "DeXian Term Deposit");
+        LendPool{ underlying_token, pool_component, dx_token, withdrawal_resource, term_deposit_resource }
+    }
+
+    pub fn pause(&mut self, badge: ResourceAddress, underlying_token: ResourceAddress, operation: ManifestOperation) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(badge)
+            .call_method(self.components.cdp_component, "pause", manifest_args!(underlying_token, operation))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn unpause(&mut self, badge: ResourceAddress, underlying_token: ResourceAddress, operation: ManifestOperation) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(badge)
+            .call_method(self.components.cdp_component, "unpause", manifest_args!(underlying_token, operation))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn is_paused(&mut self, underlying_token: ResourceAddress, operation: ManifestOperation) -> bool{
+        self.ledger.call_method(self.components.cdp_component, "is_paused", manifest_args!(underlying_token, operation))
+            .expect_commit_success().output(1)
+    }
+
+    pub fn queue_risk_param_change(&mut self, underlying_token: ResourceAddress, param: ManifestRiskParam) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.admin_badge())
+            .call_method(self.components.cdp_component, "queue_risk_param_change", manifest_args!(underlying_token, param))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn execute_risk_param_change(&mut self, change_id: u64) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.admin_badge())
+            .call_method(self.components.cdp_component, "execute_risk_param_change", manifest_args!(change_id))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn cancel_risk_param_change(&mut self, change_id: u64) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.admin_badge())
+            .call_method(self.components.cdp_component, "cancel_risk_param_change", manifest_args!(change_id))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn set_risk_param_timelock(&mut self, badge: ResourceAddress, epochs: u64) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(badge)
+            .call_method(self.components.cdp_component, "set_risk_param_timelock", manifest_args!(epochs))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn set_caps(&mut self, underlying_token: ResourceAddress, supply_cap: Option<Decimal>, borrow_cap: Option<Decimal>) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.operator_badge())
+            .call_method(self.components.cdp_component, "set_caps", manifest_args!(underlying_token, supply_cap, borrow_cap))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn set_isolation_mode(&mut self, underlying_token: ResourceAddress, is_isolated: bool, debt_ceiling: Decimal) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.admin_badge())
+            .call_method(self.components.cdp_component, "set_isolation_mode", manifest_args!(underlying_token, is_isolated, debt_ceiling))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn set_borrowable_in_isolation(&mut self, underlying_token: ResourceAddress, borrowable: bool) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.admin_badge())
+            .call_method(self.components.cdp_component, "set_borrowable_in_isolation", manifest_args!(underlying_token, borrowable))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn set_emode_category(&mut self, category_id: u8, label: &str, ltv: Decimal, liquidation_threshold: Decimal, liquidation_bonus: Decimal) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.admin_badge())
+            .call_method(self.components.cdp_component, "set_emode_category", manifest_args!(category_id, label.to_owned(), ltv, liquidation_threshold, liquidation_bonus))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn set_asset_emode(&mut self, underlying_token: ResourceAddress, category_id: Option<u8>) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.admin_badge())
+            .call_method(self.components.cdp_component, "set_asset_emode", manifest_args!(underlying_token, category_id))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn set_staking_collateral(&mut self, collateral_token: ResourceAddress, ltv: Decimal, liquidation_threshold: Decimal, liquidation_bonus: Decimal) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.admin_badge())
+            .call_method(self.components.cdp_component, "set_staking_collateral", manifest_args!(collateral_token, ltv, liquidation_threshold, liquidation_bonus))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn set_accrual_mode(&mut self, underlying_token: ResourceAddress, accrual_mode: ManifestAccrualMode) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.admin_badge())
+            .call_method(self.components.cdp_component, "set_accrual_mode", manifest_args!(underlying_token, accrual_mode))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn set_flashloan_fee_split(&mut self, underlying_token: ResourceAddress, insurance_ratio: Decimal, treasury_ratio: Decimal) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.operator_badge())
+            .call_method(self.components.cdp_component, "set_flashloan_fee_split", manifest_args!(underlying_token, insurance_ratio, treasury_ratio))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn withdraw_treasury(&mut self, underlying_token: ResourceAddress, amount: Decimal) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.operator_badge())
+            .call_method(self.components.cdp_component, "withdraw_treasury", manifest_args!(underlying_token, amount))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn withdraw_insurance(&mut self, underlying_token: ResourceAddress, amount: Decimal) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.operator_badge())
+            .call_method(self.components.cdp_component, "withdraw_insurance", manifest_args!(underlying_token, amount))
+            .build();
+        self.execute(manifest)
+    }
+
+    /// Mints the flash loan fee exempt badge into the test account.
+    pub fn mint_fee_exempt_badge(&mut self) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(self.admin_badge())
+            .mint_fungible(self.components.fee_exempt_resource, dec!(1))
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    // ---------------------------------------------------------------- lending
+
+    pub fn supply(&mut self, underlying_token: ResourceAddress, amount: Decimal) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(self.test_account, underlying_token, amount)
+            .take_all_from_worktop(underlying_token, "supply")
+            .with_bucket("supply", |builder, bucket| {
+                builder.call_method(cdp_component, "supply", manifest_args!(bucket))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn withdraw(&mut self, dx_token: ResourceAddress, amount: Decimal) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(self.test_account, dx_token, amount)
+            .take_all_from_worktop(dx_token, "dx")
+            .with_bucket("dx", |builder, bucket| {
+                builder.call_method(cdp_component, "withdraw", manifest_args!(bucket))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn request_withdrawal(&mut self, dx_token: ResourceAddress, amount: Decimal) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(self.test_account, dx_token, amount)
+            .take_all_from_worktop(dx_token, "dx")
+            .with_bucket("dx", |builder, bucket| {
+                builder.call_method(cdp_component, "request_withdrawal", manifest_args!(bucket))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn claim_withdrawal(&mut self, pool: &LendPool, request_id: NonFungibleLocalId) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, pool.withdrawal_resource, [request_id])
+            .take_all_from_worktop(pool.withdrawal_resource, "request")
+            .with_bucket("request", |builder, bucket| {
+                builder.call_method(cdp_component, "claim_withdrawal", manifest_args!(bucket))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn cancel_withdrawal(&mut self, pool: &LendPool, request_id: NonFungibleLocalId) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, pool.withdrawal_resource, [request_id])
+            .take_all_from_worktop(pool.withdrawal_resource, "request")
+            .with_bucket("request", |builder, bucket| {
+                builder.call_method(cdp_component, "cancel_withdrawal", manifest_args!(bucket))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn deposit_term(&mut self, underlying_token: ResourceAddress, amount: Decimal, weeks: u64) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(self.test_account, underlying_token, amount)
+            .take_all_from_worktop(underlying_token, "term")
+            .with_bucket("term", |builder, bucket| {
+                builder.call_method(cdp_component, "deposit_term", manifest_args!(bucket, weeks))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn redeem_term_deposit(&mut self, pool: &LendPool, deposit_id: NonFungibleLocalId) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, pool.term_deposit_resource, [deposit_id])
+            .take_all_from_worktop(pool.term_deposit_resource, "term_deposit")
+            .with_bucket("term_deposit", |builder, bucket| {
+                builder.call_method(cdp_component, "redeem_term_deposit", manifest_args!(bucket))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn process_matured_bonds(&mut self, pool: &LendPool, max_nfts: u64) -> TransactionReceipt{
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(pool.pool_component, "process_matured_bonds", manifest_args!(max_nfts))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn get_current_index(&mut self, pool: &LendPool) -> (Decimal, Decimal){
+        self.ledger.call_method(pool.pool_component, "get_current_index", manifest_args!())
+            .expect_commit_success().output(1)
+    }
+
+    /// Returns the total supply and the total outstanding loans of the pool.
+    pub fn get_total_supply_borrow(&mut self, pool: &LendPool) -> (Decimal, Decimal){
+        self.ledger.call_method(pool.pool_component, "get_total_supply_borrow", manifest_args!())
+            .expect_commit_success().output(1)
+    }
+
+    pub fn get_accrual_mode(&mut self, pool: &LendPool) -> lending::AccrualMode{
+        self.ledger.call_method(pool.pool_component, "get_accrual_mode", manifest_args!())
+            .expect_commit_success().output(1)
+    }
+
+    pub fn get_index_history(&mut self, pool: &LendPool, from: u64, to: u64) -> Vec<lending::IndexCheckpoint>{
+        self.ledger.call_method(pool.pool_component, "get_index_history", manifest_args!(from, to))
+            .expect_commit_success().output(1)
+    }
+
+    pub fn get_trailing_apy(&mut self, pool: &LendPool, days: u64) -> TransactionReceipt{
+        self.ledger.call_method(pool.pool_component, "get_trailing_apy", manifest_args!(days))
+    }
+
+    /// Borrows the flash loan and repays it together with `fee` in the same transaction, the fee exempt badge is presented if `fee_exempt`.
+    pub fn flashloan(&mut self, underlying_token: ResourceAddress, amount: Decimal, fee: Decimal, fee_exempt: bool) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let flashloan_resource = self.components.flashloan_resource;
+        let mut builder = ManifestBuilder::new()
+            .lock_fee_from_faucet();
+        if fee_exempt {
+            builder = builder
+                .create_proof_from_account_of_amount(self.test_account, self.components.fee_exempt_resource, dec!(1))
+                .pop_from_auth_zone("fee_exempt");
+        }
+        let manifest = builder
+            .with_name_lookup(|builder, lookup| {
+                let proof = if fee_exempt { Some(lookup.proof("fee_exempt")) } else { None };
+                builder.call_method(cdp_component, "borrow_flashloan", manifest_args!(underlying_token, amount, proof))
+            })
+            .withdraw_from_account(self.test_account, underlying_token, fee)
+            .take_all_from_worktop(underlying_token, "repay")
+            .take_all_from_worktop(flashloan_resource, "flashloan")
+            .with_name_lookup(|builder, lookup| {
+                builder.call_method(cdp_component, "repay_flashloan", manifest_args!(lookup.bucket("repay"), lookup.bucket("flashloan")))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    // ---------------------------------------------------------------- cdp
+
+    pub fn get_cdp(&self, cdp_id: u64) -> CollateralDebtPosition{
+        self.ledger.get_non_fungible_data(self.components.cdp_resource, NonFungibleLocalId::integer(cdp_id))
+    }
+
+    /// Queries the status of the CDP, valued by the cached oracle prices.
+    pub fn get_cdp_status(&mut self, cdp_id: u64) -> TransactionReceipt{
+        self.ledger.call_method(self.components.cdp_component, "get_cdp_status", manifest_args!(cdp_id))
+    }
+
+    pub fn borrow_variable(&mut self,
+        dx_token: ResourceAddress,
+        dx_amount: Decimal,
+        borrow_token: ResourceAddress,
+        borrow_amount: Decimal,
+        price_bundle: ManifestPriceBundle
+    ) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(self.test_account, dx_token, dx_amount)
+            .take_all_from_worktop(dx_token, "collateral")
+            .with_bucket("collateral", |builder, bucket| {
+                builder.call_method(cdp_component, "borrow_variable", manifest_args!(bucket, borrow_token, borrow_amount, price_bundle))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn borrow_stable(&mut self,
+        dx_token: ResourceAddress,
+        dx_amount: Decimal,
+        borrow_token: ResourceAddress,
+        borrow_amount: Decimal,
+        price_bundle: ManifestPriceBundle
+    ) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(self.test_account, dx_token, dx_amount)
+            .take_all_from_worktop(dx_token, "collateral")
+            .with_bucket("collateral", |builder, bucket| {
+                builder.call_method(cdp_component, "borrow_stable", manifest_args!(bucket, borrow_token, borrow_amount, price_bundle))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn extend_borrow(&mut self,
+        cdp_id: u64,
+        borrow_token: ResourceAddress,
+        amount: Decimal,
+        is_stable: bool,
+        price_bundle: ManifestPriceBundle
+    ) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let cdp_resource = self.components.cdp_resource;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, cdp_resource, [NonFungibleLocalId::integer(cdp_id)])
+            .take_all_from_worktop(cdp_resource, "cdp")
+            .with_bucket("cdp", |builder, bucket| {
+                builder.call_method(cdp_component, "extend_borrow", manifest_args!(bucket, borrow_token, amount, is_stable, price_bundle))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn delegate_credit(&mut self, cdp_id: u64, borrow_token: ResourceAddress, allowance: Decimal) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let cdp_resource = self.components.cdp_resource;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, cdp_resource, [NonFungibleLocalId::integer(cdp_id)])
+            .take_all_from_worktop(cdp_resource, "cdp")
+            .with_bucket("cdp", |builder, bucket| {
+                builder.call_method(cdp_component, "delegate_credit", manifest_args!(bucket, borrow_token, allowance))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn revoke_credit(&mut self, cdp_id: u64, delegation_id: u64) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let cdp_resource = self.components.cdp_resource;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, cdp_resource, [NonFungibleLocalId::integer(cdp_id)])
+            .take_all_from_worktop(cdp_resource, "cdp")
+            .with_bucket("cdp", |builder, bucket| {
+                builder.call_method(cdp_component, "revoke_credit", manifest_args!(bucket, delegation_id))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn extend_borrow_delegated(&mut self,
+        delegation_id: u64,
+        amount: Decimal,
+        is_stable: bool,
+        price_bundle: ManifestPriceBundle
+    ) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let delegation_resource = self.components.delegation_resource;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, delegation_resource, [NonFungibleLocalId::integer(delegation_id)])
+            .take_all_from_worktop(delegation_resource, "delegation")
+            .with_bucket("delegation", |builder, bucket| {
+                builder.call_method(cdp_component, "extend_borrow_delegated", manifest_args!(bucket, amount, is_stable, price_bundle))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn set_cdp_emode(&mut self, cdp_id: u64, category_id: Option<u8>, price_bundle: ManifestPriceBundle) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let cdp_resource = self.components.cdp_resource;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, cdp_resource, [NonFungibleLocalId::integer(cdp_id)])
+            .take_all_from_worktop(cdp_resource, "cdp")
+            .with_bucket("cdp", |builder, bucket| {
+                builder.call_method(cdp_component, "set_cdp_emode", manifest_args!(bucket, category_id, price_bundle))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn swap_rate_mode(&mut self, cdp_id: u64, borrow_token: ResourceAddress) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let cdp_resource = self.components.cdp_resource;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, cdp_resource, [NonFungibleLocalId::integer(cdp_id)])
+            .take_all_from_worktop(cdp_resource, "cdp")
+            .with_bucket("cdp", |builder, bucket| {
+                builder.call_method(cdp_component, "swap_rate_mode", manifest_args!(bucket, borrow_token))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn rebalance_stable_rate(&mut self, cdp_id: u64, borrow_token: ResourceAddress) -> TransactionReceipt{
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(self.components.cdp_component, "rebalance_stable_rate", manifest_args!(cdp_id, borrow_token))
+            .build();
+        self.execute(manifest)
+    }
+
+    /// Withdraws the `amount` (in the underlying token) of the collateral from the CDP.
+    pub fn withdraw_collateral(&mut self,
+        cdp_id: u64,
+        dx_token: ResourceAddress,
+        amount: Decimal,
+        price_bundle: ManifestPriceBundle
+    ) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let cdp_resource = self.components.cdp_resource;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, cdp_resource, [NonFungibleLocalId::integer(cdp_id)])
+            .take_all_from_worktop(cdp_resource, "cdp")
+            .with_bucket("cdp", |builder, bucket| {
+                builder.call_method(cdp_component, "withdraw_collateral", manifest_args!(bucket, dx_token, amount, price_bundle))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    /// Adds the token (underlying or dx token) to the CDP, with the proof of the CDP if `with_proof`.
+    pub fn addition_collateral(&mut self, cdp_id: u64, token: ResourceAddress, amount: Decimal, with_proof: bool) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let cdp_resource = self.components.cdp_resource;
+        let mut builder = ManifestBuilder::new().lock_fee_from_faucet();
+        if with_proof {
+            builder = builder
+                .create_proof_from_account_of_non_fungibles(self.test_account, cdp_resource, [NonFungibleLocalId::integer(cdp_id)])
+                .pop_from_auth_zone("cdp_proof");
+        }
+        let manifest = builder
+            .withdraw_from_account(self.test_account, token, amount)
+            .take_all_from_worktop(token, "collateral")
+            .with_name_lookup(|builder, lookup| {
+                let cdp_proof = if with_proof { Some(lookup.proof("cdp_proof")) } else { None };
+                builder.call_method(cdp_component, "addition_collateral", manifest_args!(cdp_id, lookup.bucket("collateral"), cdp_proof))
+            })
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn repay(&mut self, borrow_token: ResourceAddress, amount: Decimal, cdp_id: u64) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(self.test_account, borrow_token, amount)
+            .take_all_from_worktop(borrow_token, "repay")
+            .with_bucket("repay", |builder, bucket| {
+                builder.call_method(cdp_component, "repay", manifest_args!(bucket, cdp_id))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    /// Closes the CDP with a repay bucket of each amount of the borrow tokens.
+    pub fn close_cdp(&mut self, cdp_id: u64, repayments: &[(ResourceAddress, Decimal)], as_dx_token: bool) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let cdp_resource = self.components.cdp_resource;
+        let mut builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, cdp_resource, [NonFungibleLocalId::integer(cdp_id)])
+            .take_all_from_worktop(cdp_resource, "cdp");
+        for (index, (borrow_token, amount)) in repayments.iter().enumerate() {
+            builder = builder
+                .withdraw_from_account(self.test_account, *borrow_token, *amount)
+                .take_all_from_worktop(*borrow_token, format!("repay_{}", index));
+        }
+        let manifest = builder
+            .with_name_lookup(|builder, lookup| {
+                let repay_buckets: Vec<ManifestBucket> = (0..repayments.len()).map(|index| lookup.bucket(format!("repay_{}", index))).collect();
+                builder.call_method(cdp_component, "close_cdp", manifest_args!(lookup.bucket("cdp"), repay_buckets, as_dx_token))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn merge_cdps(&mut self, cdp_ids: &[u64], price_bundle: ManifestPriceBundle) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let cdp_resource = self.components.cdp_resource;
+        let mut builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, cdp_resource, cdp_ids.iter().map(|cdp_id| NonFungibleLocalId::integer(*cdp_id)));
+        for (index, cdp_id) in cdp_ids.iter().enumerate() {
+            builder = builder.take_non_fungibles_from_worktop(cdp_resource, [NonFungibleLocalId::integer(*cdp_id)], format!("cdp_{}", index));
+        }
+        let manifest = builder
+            .with_name_lookup(|builder, lookup| {
+                let cdp_buckets: Vec<ManifestBucket> = (0..cdp_ids.len()).map(|index| lookup.bucket(format!("cdp_{}", index))).collect();
+                builder.call_method(cdp_component, "merge_cdps", manifest_args!(cdp_buckets, price_bundle))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn split_cdp(&mut self, cdp_id: u64, fraction: Decimal, price_bundle: ManifestPriceBundle) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let cdp_resource = self.components.cdp_resource;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_non_fungibles_from_account(self.test_account, cdp_resource, [NonFungibleLocalId::integer(cdp_id)])
+            .take_all_from_worktop(cdp_resource, "cdp")
+            .with_bucket("cdp", |builder, bucket| {
+                builder.call_method(cdp_component, "split_cdp", manifest_args!(bucket, fraction, price_bundle))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn liquidation(&mut self,
+        borrow_token: ResourceAddress,
+        repay_amount: Decimal,
+        debt_to_cover: Decimal,
+        cdp_id: u64,
+        collateral_token: ResourceAddress,
+        price_bundle: ManifestPriceBundle
+    ) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(self.test_account, borrow_token, repay_amount)
+            .take_all_from_worktop(borrow_token, "debt")
+            .with_bucket("debt", |builder, bucket| {
+                builder.call_method(cdp_component, "liquidation", manifest_args!(bucket, debt_to_cover, cdp_id, collateral_token, price_bundle))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    /// Liquidates by the advance of the borrow pool, the advance is settled with `settle_amount` of the borrow token from the account.
+    #[allow(clippy::too_many_arguments)]
+    pub fn flash_liquidation(&mut self,
+        borrow_token: ResourceAddress,
+        debt_to_cover: Decimal,
+        cdp_id: u64,
+        collateral_token: ResourceAddress,
+        settle_amount: Option<Decimal>,
+        price_bundle: ManifestPriceBundle
+    ) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let flashloan_resource = self.components.flashloan_resource;
+        let mut builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(cdp_component, "flash_liquidation", manifest_args!(borrow_token, debt_to_cover, cdp_id, collateral_token, price_bundle, None::<ManifestProof>));
+        if let Some(amount) = settle_amount {
+            builder = builder
+                .withdraw_from_account(self.test_account, borrow_token, amount)
+                .take_all_from_worktop(borrow_token, "repay")
+                .take_all_from_worktop(flashloan_resource, "receipt")
+                .with_name_lookup(|builder, lookup| {
+                    builder.call_method(cdp_component, "repay_flashloan", manifest_args!(lookup.bucket("repay"), lookup.bucket("receipt")))
+                });
+        }
+        let manifest = builder
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    /// Borrows the `loans` in one batch flash loan, the legs are settled together with `fees` of each token from the account.
+    pub fn flashloan_batch(&mut self, loans: &[(ResourceAddress, Decimal)], fees: Option<&[(ResourceAddress, Decimal)]>) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let batch_flashloan_resource = self.components.batch_flashloan_resource;
+        let mut builder = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .call_method(cdp_component, "borrow_flashloan_batch", manifest_args!(loans.to_vec(), None::<ManifestProof>));
+        if let Some(fees) = fees {
+            for (token, fee) in fees.iter() {
+                builder = builder.withdraw_from_account(self.test_account, *token, *fee);
+            }
+            for (index, (token, _)) in loans.iter().enumerate() {
+                builder = builder.take_all_from_worktop(*token, format!("repay_{}", index));
+            }
+            builder = builder
+                .take_all_from_worktop(batch_flashloan_resource, "receipt")
+                .with_name_lookup(|builder, lookup| {
+                    let repay_buckets: Vec<ManifestBucket> = (0..loans.len()).map(|index| lookup.bucket(format!("repay_{}", index))).collect();
+                    builder.call_method(cdp_component, "repay_flashloan_batch", manifest_args!(repay_buckets, lookup.bucket("receipt")))
+                });
+        }
+        let manifest = builder
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    // ---------------------------------------------------------------- staking
+
+    /// Creates a validator staked by the test account, returns the validator and its LSU.
+    pub fn new_staked_validator(&mut self) -> (ComponentAddress, ResourceAddress){
+        let validator = self.ledger.new_staked_validator_with_pub_key(self.public_key, self.test_account);
+        let lsu = ResourceAddress::try_from(self.get_metadata_address(validator.into(), "pool_unit")).unwrap();
+        (validator, lsu)
+    }
+
+    pub fn redeem(&mut self, res_addr: ResourceAddress, amount: Decimal, faster: bool) -> TransactionReceipt{
+        let cdp_component = self.components.cdp_component;
+        let earning_component = self.components.earning_component;
+        let manifest = ManifestBuilder::new()
+            .lock_fee_from_faucet()
+            .withdraw_from_account(self.test_account, res_addr, amount)
+            .take_all_from_worktop(res_addr, "redeem")
+            .with_bucket("redeem", |builder, bucket| {
+                builder.call_method(earning_component, "redeem", manifest_args!(cdp_component, Vec::<ComponentAddress>::new(), bucket, faster))
+            })
+            .deposit_entire_worktop(self.test_account)
+            .build();
+        self.execute(manifest)
+    }
+
+    // ---------------------------------------------------------------- treasury
+
+    pub fn propose_spending(&mut self, badge: ResourceAddress, res_addr: ResourceAddress, amount: Decimal, recipient: ComponentAddress, memo: &str) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(badge)
+            .call_method(self.components.treasury_component, "propose_spending", manifest_args!(res_addr, amount, recipient, memo.to_owned()))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn approve_spending(&mut self, badge: ResourceAddress, spending_id: u64) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(badge)
+            .call_method(self.components.treasury_component, "approve_spending", manifest_args!(spending_id))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn reject_spending(&mut self, badge: ResourceAddress, spending_id: u64) -> TransactionReceipt{
+        let manifest = self.manifest_with_badge(badge)
+            .call_method(self.components.treasury_component, "reject_spending", manifest_args!(spending_id))
+            .build();
+        self.execute(manifest)
+    }
+
+    pub fn treasury_balance(&mut self, res_addr: ResourceAddress) -> Decimal{
+        self.ledger.call_method(self.components.treasury_component, "get_balance", manifest_args!(res_addr))
+            .expect_commit_success().output(1)
+    }
+
+    pub fn treasury_revenues(&mut self, res_addr: ResourceAddress) -> HashMap<RevenueSource, Decimal>{
+        self.ledger.call_method(self.components.treasury_component, "get_revenues", manifest_args!(res_addr))
+            .expect_commit_success().output(1)
+    }
+
+    pub fn pending_spendings(&mut self) -> HashMap<u64, Spending>{
+        self.ledger.call_method(self.components.treasury_component, "get_pending_spendings", manifest_args!())
+            .expect_commit_success().output(1)
+    }
+}
@@ -1 +1,88 @@
+#![allow(dead_code)]
+
 use scrypto_test::prelude::*;
+
+// the secret key of the price signer in tests, the oracle verifies the quotes by its public key.
+const PRICE_SIGNER_SECRET: [u8; 32] = [7u8; 32];
+
+/// The manifest counterpart of `SignedQuote`.
+#[derive(ManifestSbor, Clone, Debug)]
+pub struct ManifestSignedQuote{
+    pub quote: ResourceAddress,
+    pub price: String,
+    pub timestamp: u64,
+    pub signature: String
+}
+
+/// The manifest counterpart of `PriceBundle`.
+#[derive(ManifestSbor, Clone, Debug)]
+pub struct ManifestPriceBundle{
+    pub quotes: Vec<ManifestSignedQuote>
+}
+
+/// The manifest counterpart of `InterestModel`.
+#[derive(ManifestSbor, Clone, Copy, Debug)]
+pub enum ManifestInterestModel {
+    Default,
+    StableCoin,
+    XrdStaking
+}
+
+/// The manifest counterpart of `Operation`.
+#[derive(ManifestSbor, Clone, Copy, Debug)]
+pub enum ManifestOperation {
+    Supply,
+    Withdraw,
+    Borrow,
+    Liquidation,
+    FlashLoan,
+    Join,
+    Redeem
+}
+
+/// The manifest counterpart of `RiskParam`.
+#[derive(ManifestSbor, Clone, Copy, Debug)]
+pub enum ManifestRiskParam {
+    Ltv(Decimal),
+    LiquidationThreshold(Decimal),
+    LiquidationBonus(Decimal),
+    InterestModel(ManifestInterestModel),
+    InsuranceRatio(Decimal),
+    FlashloanFeeRatio(Decimal)
+}
+
+/// The manifest counterpart of `AccrualMode`.
+#[derive(ManifestSbor, Clone, Copy, Debug)]
+pub enum ManifestAccrualMode {
+    Epoch,
+    Timestamp
+}
+
+fn price_signer() -> Ed25519PrivateKey {
+    Ed25519PrivateKey::from_bytes(&PRICE_SIGNER_SECRET).unwrap()
+}
+
+pub fn price_signer_public_key() -> String {
+    price_signer().public_key().to_string()
+}
+
+///
+/// Signs the XRD price in the quote token the way the oracle verifies it: "{XRD}/{quote}{price}{epoch}{timestamp}".
+pub fn sign_price_quote(quote: ResourceAddress, xrd_price_in_quote: &str, epoch: u64, timestamp: u64) -> ManifestSignedQuote {
+    let encoder = AddressBech32Encoder::for_simulator();
+    let message = format!(
+        "{base}/{quote}{price}{epoch}{timestamp}",
+        base = XRD.to_string(&encoder),
+        quote = quote.to_string(&encoder),
+        price = xrd_price_in_quote,
+        epoch = epoch,
+        timestamp = timestamp
+    );
+    let signature = price_signer().sign(message.as_bytes());
+    ManifestSignedQuote{
+        quote,
+        price: xrd_price_in_quote.to_owned(),
+        timestamp,
+        signature: signature.to_string()
+    }
+}